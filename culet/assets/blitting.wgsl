@@ -1,5 +1,12 @@
 @group(0) @binding(0) var ray_trace_output: texture_2d<f32>;
 @group(0) @binding(1) var<uniform> viewport_dimensions: vec2u;
+@group(0) @binding(2) var<uniform> focus_peaking: FocusPeaking;
+
+struct FocusPeaking {
+    color: vec3f,
+    focal_distance: f32,
+    band: f32, // 0 disables the overlay
+}
 
 struct FullscreenVertexOutput {
     @builtin(position)
@@ -19,5 +26,11 @@ fn fragment(@builtin(position) in: vec4f) -> @location(0) vec4<f32> {
     let texture_dimensions = textureDimensions(ray_trace_output) ;
     let x_start = (i32(viewport_dimensions.x) - i32(texture_dimensions.x)) / 2;
     let y_start = (i32(viewport_dimensions.y) - i32(texture_dimensions.y)) / 2;
-    return textureLoad(ray_trace_output, vec2i(i32(in.x) - x_start, i32(in.y) - y_start), 0);
+    // alpha holds the primary hit distance, 0 where the ray escaped, not coverage
+    let texel = textureLoad(ray_trace_output, vec2i(i32(in.x) - x_start, i32(in.y) - y_start), 0);
+    var color = texel.rgb;
+    if texel.a > 0.0 && abs(texel.a - focus_peaking.focal_distance) < focus_peaking.band {
+        color = mix(color, focus_peaking.color, 0.5);
+    }
+    return vec4(color, 1.0);
 }
\ No newline at end of file
@@ -4,6 +4,12 @@
 @group(0) @binding(3) var<storage> bvh_nodes: array<BvhNode>;
 @group(0) @binding(4) var<uniform> camera: Camera;
 @group(0) @binding(5) var output: texture_storage_2d<rgba32float, read_write>;
+@group(0) @binding(6) var environment: texture_2d<f32>;
+@group(0) @binding(7) var<uniform> environment_params: Environment;
+@group(0) @binding(8) var<uniform> material: Material;
+@group(0) @binding(9) var<uniform> lighting: Lighting;
+@group(0) @binding(10) var previous_frame: texture_2d<f32>;
+@group(0) @binding(11) var<uniform> temporal: Temporal;
 
 struct BvhNode {
     aabb_min: vec3f,
@@ -12,6 +18,32 @@ struct BvhNode {
     triangle_count: u32,
 }
 
+struct Material {
+    absorption: vec3f,
+    refractive_index: f32,
+    dispersion: f32, // not applied yet, the GPU path traces a single wavelength
+}
+
+struct Environment {
+    enabled: u32,
+    rotation: f32, // radians about +Y
+}
+
+struct Lighting {
+    sharpness: f32, // 0 isometric, 1 cosine, otherwise hemisphere
+    head_shadow_degrees: f32,
+}
+
+struct Temporal {
+    previous_origin: vec3f,
+    previous_look_dir: vec3f,
+    previous_up: vec3f,
+    previous_fov: f32,
+    jitter: vec2f,
+    history_weight: f32, // 0 discards the previous frame
+    depth_tolerance: f32,
+}
+
 struct Camera {
     origin: vec3f,
     look_dir: vec3f,
@@ -198,11 +230,37 @@ fn intersect_scene(ray: Ray) -> HitInfo {
 }
 
 fn lighting_model(direction: vec3f) -> vec3f {
-    var cos = max(-dot(direction, camera.look_dir), 0.0);
-    if degrees(acos(cos)) < 10.0 {
-        cos = 0.0;
+    let cos = -dot(direction, camera.look_dir);
+    // pow is undefined for a zero base with a non-positive exponent, so grazing directions
+    // go dark here rather than through pow(0, 0) with the isometric sharpness
+    if degrees(acos(clamp(cos, -1.0, 1.0))) < lighting.head_shadow_degrees || cos <= 0.0 {
+        return vec3f(0.0);
     }
-    return vec3f(1.0 * cos); // TODO: configurable light intensity
+    return vec3f(1.0 * pow(cos, lighting.sharpness)); // TODO: configurable light intensity
+}
+
+// equirectangular lookup, +Y is up. the map is turned by rotating the direction the other way
+fn sample_environment(world_direction: vec3f) -> vec3f {
+    let c = cos(environment_params.rotation);
+    let s = sin(environment_params.rotation);
+    let direction = vec3f(
+        c * world_direction.x - s * world_direction.z,
+        world_direction.y,
+        s * world_direction.x + c * world_direction.z,
+    );
+    let dims = textureDimensions(environment);
+    let u = atan2(direction.z, direction.x) / (2.0 * 3.14159265) + 0.5;
+    let v = acos(clamp(direction.y, -1.0, 1.0)) / 3.14159265;
+    let texel = min(vec2u(vec2f(u, v) * vec2f(dims)), dims - 1u);
+    return textureLoad(environment, texel, 0).rgb;
+}
+
+// light arriving along a ray that escapes the stone
+fn escaped_light(direction: vec3f) -> vec3f {
+    if environment_params.enabled != 0u {
+        return sample_environment(direction);
+    }
+    return lighting_model(direction);
 }
 
 fn fresnel(incoming: vec3f, normal: vec3f, eta_i: f32, eta_t: f32) -> f32 {
@@ -226,21 +284,19 @@ struct ColorListEntry {
     reflection_ratio: f32,
 }
 
-fn trace(pixel_ray: Ray, max_depth: u32) -> vec3f {
+fn trace(pixel_ray: Ray, first_surface_hit: HitInfo, max_depth: u32) -> vec3f {
     var refraction_colors = array<vec3f, 16>();
     var reflection_info = array<ColorListEntry, 16>();
     var reflection_color = vec3f();
 
-    var ri = 2.16; // TODO: configurable & dispersion
+    let ri = material.refractive_index;
     var light_color = vec3(1.0);
 
-    let first_surface_hit = intersect_scene(pixel_ray);
-
     if first_surface_hit.ray_distance != 1e20 {
         reflection_info[0] = ColorListEntry(first_surface_hit.ray_distance, fresnel(pixel_ray.direction, first_surface_hit.normal, 1.0, ri));
 
         let first_surface_reflection = normalize(reflect(pixel_ray.direction, first_surface_hit.normal));
-        reflection_color = lighting_model(first_surface_reflection) * light_color;
+        reflection_color = escaped_light(first_surface_reflection) * light_color;
 
         var ray = Ray(first_surface_hit.position, normalize(refract(pixel_ray.direction, first_surface_hit.normal, 1.0 / ri)));
         for (var i = 1u; i < max_depth; i++) {
@@ -256,7 +312,7 @@ fn trace(pixel_ray: Ray, max_depth: u32) -> vec3f {
 
             if reflection_ratio != 1.0 {
                 let refraction_direction = normalize(refract(ray.direction, -hit.normal, ri));
-                refraction_colors[i] = lighting_model(refraction_direction) * light_color;
+                refraction_colors[i] = escaped_light(refraction_direction) * light_color;
             }
 
             ray = Ray(hit.position, reflection_direction);
@@ -270,30 +326,78 @@ fn trace(pixel_ray: Ray, max_depth: u32) -> vec3f {
     let start = max_depth - 1;
     for (var i = start; i > 0u; i--) {
         let refraction_color = refraction_colors[i] * (1.0 - reflection_info[i].reflection_ratio);
-            // TODO: configurable attenuation
-        color = refraction_color + color * reflection_info[i].reflection_ratio * exp(-vec3f(0.0, 2.0, 5.0) * reflection_info[i].ray_distance);
+        // light leaving at bounce i, and everything reflected back into it there, crossed the
+        // interior segment ending at bounce i, so both are attenuated by Beer's law over it
+        color = (refraction_color + color * reflection_info[i].reflection_ratio) * exp(-material.absorption * reflection_info[i].ray_distance);
     }
 
     color = reflection_color * reflection_info[0].reflection_ratio + color * (1.0 - reflection_info[0].reflection_ratio);
     return color;
 }
 
+// camera up orthogonalised against the view direction
+fn view_up(up: vec3f, look_dir: vec3f) -> vec3f {
+    let view_up = normalize(cross(cross(up, look_dir), look_dir));
+    if dot(view_up, up) < 1e-7 {
+        return -view_up;
+    }
+    return view_up;
+}
+
+// previous frame's color at the pixel that saw `position`, with the confidence that it saw the
+// same surface in alpha. the stored hit distance has to agree, otherwise it was occluded
+fn reproject(position: vec3f, dims: vec2f) -> vec4f {
+    if temporal.history_weight == 0.0 {
+        return vec4f(0.0);
+    }
+
+    let offset = position - temporal.previous_origin;
+    let z = dot(offset, temporal.previous_look_dir);
+    if z <= 0.0 {
+        return vec4f(0.0);
+    }
+    let up = view_up(temporal.previous_up, temporal.previous_look_dir);
+    let left = normalize(cross(temporal.previous_up, temporal.previous_look_dir));
+    let tan_half_height = tan(temporal.previous_fov / 2.0);
+    let tan_half_width = tan_half_height * dims.x / dims.y;
+    let screen = vec2f(dot(offset, left) / tan_half_width, dot(offset, up) / tan_half_height) / z;
+    let pixel = (1.0 - screen) * dims / 2.0;
+    if any(pixel < vec2f(0.0)) || any(pixel >= dims) {
+        return vec4f(0.0);
+    }
+
+    // a stored distance of 0 means the previous ray escaped
+    let previous = textureLoad(previous_frame, vec2i(pixel), 0);
+    let expected = length(offset);
+    let error = abs(previous.a - expected) / expected;
+    let confidence = 1.0 - clamp(error / temporal.depth_tolerance, 0.0, 1.0);
+    return vec4f(previous.rgb, confidence);
+}
+
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) id: vec3u) {
     let focal_length = 0.1;
-    let edge_distance = focal_length * tan(camera.fov / 2.0);
-    var up = normalize(cross(cross(camera.up, camera.look_dir), camera.look_dir));
-    if dot(up, camera.up) < 1e-7 {
-        up = -up;
-    }
+    // fov is vertical, widen horizontally to the output's aspect ratio
+    let dims = vec2f(textureDimensions(output));
+    let half_height = focal_length * tan(camera.fov / 2.0);
+    let half_width = half_height * dims.x / dims.y;
+    let up = view_up(camera.up, camera.look_dir);
     let left = normalize(cross(camera.up, camera.look_dir));
-    let top_left = camera.origin + camera.look_dir * focal_length + (left + up) * edge_distance;
-    let pixel_x_delta = left * -2.0 * edge_distance / f32(textureDimensions(output).x);
-    let pixel_y_delta = up * -2.0 * edge_distance / f32(textureDimensions(output).y);
-    let pixel_position = top_left + f32(id.x) * pixel_x_delta + f32(id.y) * pixel_y_delta;
+    let top_left = camera.origin + camera.look_dir * focal_length + left * half_width + up * half_height;
+    let pixel_x_delta = left * -2.0 * half_width / dims.x;
+    let pixel_y_delta = up * -2.0 * half_height / dims.y;
+    let pixel = vec2f(id.xy) + temporal.jitter;
+    let pixel_position = top_left + pixel.x * pixel_x_delta + pixel.y * pixel_y_delta;
 
     let ray = Ray(camera.origin, normalize(pixel_position - camera.origin));
-
-    let color = trace(ray, 10u); // TODO: configurable max bounces
-    textureStore(output, vec2(i32(id.x), i32(id.y)), vec4(color, 1.0));
+    let first_hit = intersect_scene(ray);
+
+    var color = trace(ray, first_hit, 10u); // TODO: configurable max bounces
+    var depth = 0.0;
+    if first_hit.ray_distance != 1e20 {
+        depth = first_hit.ray_distance;
+        let previous = reproject(first_hit.position, dims);
+        color = mix(color, previous.rgb, temporal.history_weight * previous.a);
+    }
+    textureStore(output, vec2(i32(id.x), i32(id.y)), vec4(color, depth));
 }
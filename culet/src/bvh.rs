@@ -1,3 +1,7 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
 use bevy::prelude::*;
 use bevy::render::render_resource::{ShaderType, StorageBuffer};
 
@@ -14,6 +18,22 @@ pub struct BvhNode {
     triangle_count: u32, // align 4
 }
 
+// pairs of corners, indexed as in `export_aabbs_obj`, that differ along exactly one axis
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
 pub struct Bvh {
     vertices: Vec<Vec4>, // Vec4 for GPU alignment
     indices: Vec<u32>,
@@ -129,6 +149,68 @@ impl Bvh {
         self.subdivide(right_child as u32);
     }
 
+    // writes every node's bounds as a wireframe box, one `depth_N` group and material per
+    // tree level. the materials go in a .mtl next to the obj, shading from blue at the root
+    // to red at the deepest leaves
+    pub fn export_aabbs_obj(&self, path: &Path) -> io::Result<()> {
+        // (node, depth) for every node reachable from the root
+        let mut visited = Vec::with_capacity(self.node_count as usize);
+        let mut stack = vec![(0usize, 0usize)];
+        while let Some((index, depth)) = stack.pop() {
+            visited.push((index, depth));
+            let node = &self.nodes[index];
+            if node.triangle_count == 0 {
+                let left = node.left_or_first as usize;
+                stack.push((left + 1, depth + 1));
+                stack.push((left, depth + 1));
+            }
+        }
+        visited.sort_by_key(|&(_, depth)| depth);
+        let max_depth = visited.last().map_or(0, |&(_, depth)| depth);
+
+        let mtl_path = path.with_extension("mtl");
+        let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+        for depth in 0..=max_depth {
+            let t = depth as f32 / max_depth.max(1) as f32;
+            writeln!(mtl, "newmtl depth_{depth}")?;
+            writeln!(mtl, "Kd {} 0 {}", t, 1.0 - t)?;
+        }
+        mtl.flush()?;
+
+        let mut obj = BufWriter::new(File::create(path)?);
+        if let Some(name) = mtl_path.file_name() {
+            writeln!(obj, "mtllib {}", name.to_string_lossy())?;
+        }
+        let mut current_depth = None;
+        for (i, &(index, depth)) in visited.iter().enumerate() {
+            if current_depth != Some(depth) {
+                writeln!(obj, "g depth_{depth}")?;
+                writeln!(obj, "usemtl depth_{depth}")?;
+                current_depth = Some(depth);
+            }
+            let node = &self.nodes[index];
+            // corner k takes max on the axes whose bit is set in k
+            for k in 0..8 {
+                let corner = Vec3::select(
+                    BVec3::new(k & 1 != 0, k & 2 != 0, k & 4 != 0),
+                    node.aabb_max,
+                    node.aabb_min,
+                );
+                writeln!(obj, "v {} {} {}", corner.x, corner.y, corner.z)?;
+            }
+            // obj indices are 1-based and count every vertex written so far
+            let base = i * 8 + 1;
+            for (a, b) in BOX_EDGES {
+                writeln!(obj, "l {} {}", base + a, base + b)?;
+            }
+        }
+        obj.flush()
+    }
+
+    pub fn node_count(&self) -> u32 {
+        self.node_count
+    }
+
     pub fn gpu_buffers(
         self,
     ) -> (
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+// size of the built-in studio environment, 2:1 like any equirectangular map
+const STUDIO_WIDTH: u32 = 512;
+const STUDIO_HEIGHT: u32 = 256;
+
+// decode an .hdr or .exr (or any other format `image` knows) into the float texture the
+// tracer samples. radiance is kept linear, LDR files are taken as already linear
+pub fn load_environment(path: &Path) -> image::ImageResult<Image> {
+    let decoded = image::open(path)?.into_rgba32f();
+    let (width, height) = decoded.dimensions();
+    Ok(environment_image(width, height, decoded.into_raw()))
+}
+
+// a dim grey room with a large softbox overhead and two strip lights either side, so the
+// environment lighting works without having to find an HDR first
+pub fn studio_environment() -> Image {
+    let mut texels = Vec::with_capacity((STUDIO_WIDTH * STUDIO_HEIGHT * 4) as usize);
+    for y in 0..STUDIO_HEIGHT {
+        // same mapping as the shader: v = 0 is straight up, u wraps around +Y from -X
+        let theta = (y as f32 + 0.5) / STUDIO_HEIGHT as f32 * std::f32::consts::PI;
+        for x in 0..STUDIO_WIDTH {
+            let phi = ((x as f32 + 0.5) / STUDIO_WIDTH as f32 - 0.5) * std::f32::consts::TAU;
+            let direction = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            let radiance = studio_radiance(direction);
+            texels.extend_from_slice(&[radiance.x, radiance.y, radiance.z, 1.0]);
+        }
+    }
+    environment_image(STUDIO_WIDTH, STUDIO_HEIGHT, texels)
+}
+
+fn studio_radiance(direction: Vec3) -> Vec3 {
+    // floor darker than the walls, walls a little brighter towards the ceiling
+    let mut radiance = if direction.y < 0.0 {
+        Vec3::splat(0.05)
+    } else {
+        Vec3::splat(0.1 + 0.15 * direction.y)
+    };
+    // overhead softbox, ~35 degrees across
+    if direction.y > 0.95 {
+        radiance += Vec3::splat(8.0);
+    }
+    // vertical strips at +-X, slightly warm and slightly cool
+    let height = direction.y.abs() < 0.5;
+    if height && direction.x > 0.97 {
+        radiance += Vec3::new(6.0, 5.5, 5.0);
+    }
+    if height && direction.x < -0.97 {
+        radiance += Vec3::new(4.5, 5.0, 6.0);
+    }
+    radiance
+}
+
+fn environment_image(width: u32, height: u32, texels: Vec<f32>) -> Image {
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bytemuck::cast_slice(&texels).to_vec(),
+        TextureFormat::Rgba32Float,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use culet_lib::{
+    glam::Vec3,
+    mesh::{Mesh, MeshLoadError},
+};
+
+use crate::bvh::Bvh;
+
+// vertical fov of the viewer's cameras, for the suggested framing distance
+const VIEWER_FOV_DEGREES: f32 = 45.0;
+
+fn load_mesh(path: &Path) -> Result<Mesh, MeshLoadError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf" | "glb") => Mesh::load_from_gltf(Vec3::ZERO, path),
+        Some("ply") => Mesh::load_from_ply(Vec3::ZERO, path),
+        Some("obj") => Mesh::load_from_obj(Vec3::ZERO, path),
+        _ => Ok(Mesh::load_from_stl(Vec3::ZERO, path)),
+    }
+}
+
+// `culet --info <mesh>` prints what was loaded instead of opening the viewer
+pub fn print_mesh_info(path: &Path) -> Result<(), MeshLoadError> {
+    let mesh = load_mesh(path)?;
+
+    let report = mesh.validate();
+    let (min, max) = (mesh.bounding_box().min(), mesh.bounding_box().max());
+    let dimensions = max - min;
+    // far enough for the bounding sphere to fit the fov
+    let radius = dimensions.length() / 2.0;
+    let distance = radius / (VIEWER_FOV_DEGREES.to_radians() / 2.0).sin();
+
+    println!("{}", path.display());
+    println!("  triangles:           {}", mesh.triangle_count());
+    println!("  bounding box min:    {:.4}", min);
+    println!("  bounding box max:    {:.4}", max);
+    println!("  dimensions:          {:.4}", dimensions);
+    println!("  centroid:            {:.4}", mesh.centroid());
+    println!("  degenerate:          {}", report.degenerate);
+    println!("  duplicate:           {}", report.duplicate);
+    println!("  non-manifold edges:  {}", report.non_manifold_edges);
+    println!(
+        "  normals consistent:  {}",
+        if report.inconsistent_edges == 0 {
+            "yes".to_string()
+        } else {
+            format!("no, {} edges disagree", report.inconsistent_edges)
+        }
+    );
+    // only meaningful for closed meshes
+    if report.non_manifold_edges == 0 {
+        let outward = mesh.signed_volume() > 0.0;
+        println!(
+            "  normals face:        {}",
+            if outward { "outward" } else { "inward" }
+        );
+    }
+    println!(
+        "  camera distance:     {:.4} ({VIEWER_FOV_DEGREES} degree fov)",
+        distance
+    );
+    Ok(())
+}
+
+// `culet --bvh-obj <mesh> <out.obj>` builds the viewer's BVH for a mesh and writes its
+// bounds out for inspection next to the mesh in another viewer
+pub fn export_bvh_obj(mesh_path: &Path, obj_path: &Path) -> Result<(), MeshLoadError> {
+    let mesh = load_mesh(mesh_path)?;
+    // unindexed, each triangle gets its own three vertices
+    let vertices: Vec<_> = mesh
+        .triangle_slice()
+        .iter()
+        .flat_map(|triangle| triangle.points())
+        .map(|p| bevy::math::Vec3::new(p.x, p.y, p.z))
+        .collect();
+    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+
+    let bvh = Bvh::new(&vertices, &indices);
+    bvh.export_aabbs_obj(obj_path)?;
+    println!("wrote {} nodes to {}", bvh.node_count(), obj_path.display());
+    Ok(())
+}
@@ -3,33 +3,109 @@ use bevy::{
         fxaa::{Fxaa, Sensitivity},
         prepass::{DepthPrepass, NormalPrepass},
     },
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
     pbr::wireframe::{WireframeConfig, WireframePlugin},
     prelude::*,
-    render::camera::CameraRenderGraph,
+    render::{camera::CameraRenderGraph, primitives::Aabb},
 };
 use bevy_panorbit_camera::*;
 use bevy_stl::StlPlugin;
-use ray_tracing::{CuletCamera, CuletGraph, CuletMesh, CuletPlugin};
+use culet_lib::material::GEM_RI_PRESETS;
+use ray_tracing::{
+    CuletCamera, CuletEnvironment, CuletFocusPeaking, CuletFrameCapture, CuletGraph, CuletLighting,
+    CuletLightingModel, CuletMaterial, CuletMesh, CuletPlugin, CuletTemporalAccumulation,
+};
 
 mod bvh;
+mod environment;
+mod info;
 mod ray_tracing;
 
 fn main() {
-    App::new()
-        .insert_resource(Msaa::Off)
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--info") {
+        let Some(path) = args.get(i + 1) else {
+            eprintln!("usage: culet --info <mesh.stl|.gltf|.glb|.ply|.obj>");
+            std::process::exit(2);
+        };
+        if let Err(e) = info::print_mesh_info(path.as_ref()) {
+            eprintln!("{path}: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--bvh-obj") {
+        let (Some(mesh_path), Some(obj_path)) = (args.get(i + 1), args.get(i + 2)) else {
+            eprintln!("usage: culet --bvh-obj <mesh.stl|.gltf|.glb|.ply|.obj> <out.obj>");
+            std::process::exit(2);
+        };
+        if let Err(e) = info::export_bvh_obj(mesh_path.as_ref(), obj_path.as_ref()) {
+            eprintln!("{mesh_path}: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    // decoded up front so a bad file fails like the other arguments rather than in a log line
+    let initial_environment = match args.iter().position(|arg| arg == "--env") {
+        Some(i) => {
+            let Some(path) = args.get(i + 1) else {
+                eprintln!("usage: culet --env <environment.hdr|.exr>");
+                std::process::exit(2);
+            };
+            match environment::load_environment(path.as_ref()) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    eprintln!("{path}: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut app = App::new();
+    app.insert_resource(Msaa::Off)
         .add_plugins(DefaultPlugins)
         .add_plugins(PanOrbitCameraPlugin)
         .add_plugins(StlPlugin)
         .add_plugins(WireframePlugin)
         .add_plugins(CuletPlugin)
+        .add_plugins((FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin::default()))
         .add_systems(Startup, setup)
-        .add_systems(Update, switch_cameras)
-        .run();
+        .add_systems(
+            Update,
+            (
+                switch_cameras,
+                keyboard_zoom,
+                focus_on_mesh,
+                capture_frame,
+                toggle_head_shadow,
+                cycle_lighting_model,
+                toggle_temporal_accumulation,
+                focus_peaking,
+                rotate_environment,
+                load_dropped_environment,
+                toggle_studio_environment,
+                cycle_gem_preset,
+            ),
+        );
+    if let Some(image) = initial_environment {
+        let handle = app.world.resource_mut::<Assets<Image>>().add(image);
+        app.world.resource_mut::<CuletEnvironment>().image = Some(handle);
+    }
+    app.run();
 }
 
 #[derive(Component)]
 pub struct CadCamera;
 
+// closest the keyboard zoom will take the camera to its focus point
+const MIN_ZOOM_RADIUS: f32 = 0.5;
+// fraction of the orbit radius travelled per second while a zoom key is held
+const KEYBOARD_ZOOM_SPEED: f32 = 1.0;
+// degrees per second the environment turns while a rotation key is held
+const ENVIRONMENT_ROTATION_SPEED: f32 = 45.0;
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -65,6 +141,7 @@ fn setup(
             ..default()
         },
         CuletMesh,
+        CuletMaterial::default(),
     ));
 
     // CAD wireframe camera
@@ -77,10 +154,11 @@ fn setup(
             ..default()
         },
         PanOrbitCamera {
+            // re-centred on the mesh once it has loaded
             focus: Vec3::new(0.0, 0.0, 0.0),
             radius: Some(5.0),
             orbit_sensitivity: 0.5,
-            pan_sensitivity: 0.0,
+            button_pan: MouseButton::Middle,
             ..default()
         },
         DepthPrepass,
@@ -104,7 +182,10 @@ fn setup(
             camera_render_graph: CameraRenderGraph::new(CuletGraph),
             ..default()
         },
-        PanOrbitCamera { ..default() },
+        PanOrbitCamera {
+            button_pan: MouseButton::Middle,
+            ..default()
+        },
         CuletCamera,
         Fxaa {
             enabled: true,
@@ -168,3 +249,186 @@ fn switch_cameras(
         active_cam.order = 0;
     }
 }
+
+// scroll zoom is handled by PanOrbitCamera, this adds +/- keys for dollying in and out
+fn keyboard_zoom(
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    let zoom_in = keys.any_pressed([KeyCode::Equal, KeyCode::NumpadAdd]);
+    let zoom_out = keys.any_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]);
+    if zoom_in == zoom_out {
+        return;
+    }
+
+    let step = KEYBOARD_ZOOM_SPEED * time.delta_seconds();
+    let scale = if zoom_in { 1.0 - step } else { 1.0 + step };
+
+    // keep both cameras in sync so switching doesn't jump
+    for mut camera in cameras.iter_mut() {
+        camera.target_radius = (camera.target_radius * scale).max(MIN_ZOOM_RADIUS);
+    }
+}
+
+// orbit around the centre of the loaded mesh rather than the world origin
+fn focus_on_mesh(
+    mut events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    culet_mesh: Query<(&Handle<Mesh>, &GlobalTransform), With<CuletMesh>>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    let Ok((handle, transform)) = culet_mesh.get_single() else {
+        return;
+    };
+
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(handle) {
+            continue;
+        }
+        let Some(Aabb { center, .. }) = meshes.get(handle).and_then(Mesh::compute_aabb) else {
+            continue;
+        };
+        let center = transform.transform_point(center.into());
+        for mut camera in cameras.iter_mut() {
+            camera.target_focus = center;
+        }
+    }
+}
+
+// F12 writes the next ray-traced frame to a PNG in the working directory
+fn capture_frame(
+    keys: Res<ButtonInput<KeyCode>>,
+    culet_cam: Query<&Camera, With<CuletCamera>>,
+    mut capture: ResMut<CuletFrameCapture>,
+) {
+    if !keys.just_pressed(KeyCode::F12) {
+        return;
+    }
+    // the compute pass only runs while the ray-tracing camera is active
+    if culet_cam.get_single().is_ok_and(|camera| camera.is_active) {
+        capture.requests += 1;
+    } else {
+        warn!("switch to the ray-tracing camera (Space) to capture a frame");
+    }
+}
+
+// H switches the observer's head shadow off and back on
+fn toggle_head_shadow(keys: Res<ButtonInput<KeyCode>>, mut lighting: ResMut<CuletLighting>) {
+    if keys.just_pressed(KeyCode::KeyH) {
+        lighting.head_shadow_degrees = if lighting.head_shadow_degrees > 0.0 {
+            0.0
+        } else {
+            CuletLighting::default().head_shadow_degrees
+        };
+    }
+}
+
+// L steps through the lighting models for rays leaving the stone
+fn cycle_lighting_model(keys: Res<ButtonInput<KeyCode>>, mut lighting: ResMut<CuletLighting>) {
+    if keys.just_pressed(KeyCode::KeyL) {
+        lighting.model = match lighting.model {
+            CuletLightingModel::Cosine => CuletLightingModel::Hemisphere { sharpness: 4.0 },
+            CuletLightingModel::Hemisphere { .. } => CuletLightingModel::Isometric,
+            CuletLightingModel::Isometric => CuletLightingModel::Cosine,
+        };
+        info!("lighting model: {:?}", lighting.model);
+    }
+}
+
+// T switches accumulating frames over time off, e.g. to compare against single frames
+fn toggle_temporal_accumulation(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut temporal: ResMut<CuletTemporalAccumulation>,
+) {
+    if keys.just_pressed(KeyCode::KeyT) {
+        temporal.enabled = !temporal.enabled;
+        info!("temporal accumulation: {}", temporal.enabled);
+    }
+}
+
+// F toggles highlighting the surfaces at the orbit focus distance, which follows the zoom
+fn focus_peaking(
+    keys: Res<ButtonInput<KeyCode>>,
+    culet_cam: Query<&PanOrbitCamera, With<CuletCamera>>,
+    mut focus_peaking: ResMut<CuletFocusPeaking>,
+) {
+    if keys.just_pressed(KeyCode::KeyF) {
+        focus_peaking.enabled = !focus_peaking.enabled;
+    }
+    if let Some(radius) = culet_cam.get_single().ok().and_then(|camera| camera.radius) {
+        if focus_peaking.focal_distance != radius {
+            focus_peaking.focal_distance = radius;
+        }
+    }
+}
+
+// [ and ] turn the environment around the stone to find the best sparkle, the camera stays put
+fn rotate_environment(
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut environment: ResMut<CuletEnvironment>,
+) {
+    let left = keys.pressed(KeyCode::BracketLeft);
+    let right = keys.pressed(KeyCode::BracketRight);
+    if left == right {
+        return;
+    }
+    let step = ENVIRONMENT_ROTATION_SPEED * time.delta_seconds();
+    let rotation = environment.rotation_degrees + if right { step } else { -step };
+    environment.rotation_degrees = rotation.rem_euclid(360.0);
+}
+
+// dropping an .hdr or .exr onto the window replaces the environment
+fn load_dropped_environment(
+    mut events: EventReader<FileDragAndDrop>,
+    mut images: ResMut<Assets<Image>>,
+    mut environment: ResMut<CuletEnvironment>,
+) {
+    for event in events.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = event else {
+            continue;
+        };
+        match environment::load_environment(path_buf) {
+            Ok(image) => {
+                environment.image = Some(images.add(image));
+                info!("environment: {}", path_buf.display());
+            }
+            Err(e) => error!("couldn't load environment {}: {e}", path_buf.display()),
+        }
+    }
+}
+
+// E switches the environment off, or on with the built-in studio lights
+fn toggle_studio_environment(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut images: ResMut<Assets<Image>>,
+    mut environment: ResMut<CuletEnvironment>,
+) {
+    if keys.just_pressed(KeyCode::KeyE) {
+        environment.image = match environment.image {
+            Some(_) => None,
+            None => Some(images.add(environment::studio_environment())),
+        };
+    }
+}
+
+// G steps the stone's refractive index through the common gem presets, starting over after
+// the last one. an index that isn't a preset goes to the first
+fn cycle_gem_preset(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut materials: Query<&mut CuletMaterial, With<CuletMesh>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    for mut material in materials.iter_mut() {
+        let next = GEM_RI_PRESETS
+            .iter()
+            .position(|&(_, ri)| ri == material.refractive_index)
+            .map_or(0, |i| (i + 1) % GEM_RI_PRESETS.len());
+        let (name, ri) = GEM_RI_PRESETS[next];
+        material.refractive_index = ri;
+        info!("gem preset: {name} (RI {ri})");
+    }
+}
@@ -1,12 +1,15 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::mpsc::channel};
 
 use bevy::{
     core_pipeline::{core_3d::graph::Node3d, fxaa::FxaaNode, upscaling::UpscalingNode},
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
     ecs::query::QueryItem,
     prelude::*,
     render::{
         camera::{CameraOutputMode, ExtractedCamera},
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
         mesh::{PrimitiveTopology, VertexAttributeValues},
+        render_asset::RenderAssets,
         render_graph::{
             NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, RenderSubGraph,
             ViewNode, ViewNodeRunner,
@@ -15,9 +18,10 @@ use bevy::{
             binding_types::{
                 storage_buffer_read_only, texture_2d, texture_storage_2d, uniform_buffer,
             },
-            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedComputePipelineId,
-            CachedRenderPipelineId, ColorTargetState, ColorWrites, ComputePassDescriptor,
-            ComputePipelineDescriptor, Extent3d, FragmentState, FrontFace, LoadOp,
+            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, Buffer, BufferDescriptor,
+            BufferUsages, CachedComputePipelineId, CachedRenderPipelineId, ColorTargetState,
+            ColorWrites, ComputePassDescriptor, ComputePipelineDescriptor, Extent3d, FragmentState,
+            FrontFace, ImageCopyBuffer, ImageDataLayout, LoadOp, Maintain, MapMode,
             MultisampleState, Operations, PipelineCache, PolygonMode, PrimitiveState,
             RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
             ShaderStages, ShaderType, StorageBuffer, StorageTextureAccess, StoreOp, Texture,
@@ -25,16 +29,85 @@ use bevy::{
             TextureUsages, TextureViewDescriptor, TextureViewDimension, UniformBuffer, VertexState,
         },
         renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::FallbackImage,
         view::ViewTarget,
         Extract, Render, RenderApp, RenderSet,
     },
 };
+use culet_lib::color::linear_to_srgb;
 
 use crate::bvh::{Bvh, BvhNode};
 
 #[derive(Component)]
 pub struct CuletMesh;
 
+// stone material for the GPU tracer, put on the CuletMesh entity to edit it live
+#[derive(Component, Copy, Clone, Debug)]
+pub struct CuletMaterial {
+    pub refractive_index: f32,
+    // per-unit-distance absorption inside the stone, as in the CPU Material::Refractive color
+    pub absorption: Vec3,
+    pub dispersion: f32,
+}
+
+impl Default for CuletMaterial {
+    fn default() -> Self {
+        Self {
+            refractive_index: 2.16,
+            absorption: Vec3::new(0.0, 2.0, 5.0),
+            dispersion: 0.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Resource, ShaderType)]
+pub struct CuletMaterialParams {
+    absorption: Vec3,
+    refractive_index: f32,
+    dispersion: f32,
+}
+
+impl From<CuletMaterial> for CuletMaterialParams {
+    fn from(material: CuletMaterial) -> Self {
+        Self {
+            absorption: material.absorption,
+            refractive_index: material.refractive_index,
+            dispersion: material.dispersion,
+        }
+    }
+}
+
+impl FromWorld for CuletMaterialParams {
+    fn from_world(_world: &mut World) -> Self {
+        CuletMaterial::default().into()
+    }
+}
+
+fn extract_material_params(
+    mut commands: Commands,
+    material: Extract<Query<&CuletMaterial, With<CuletMesh>>>,
+) {
+    let material = material.get_single().copied().unwrap_or_default();
+    commands.insert_resource(CuletMaterialParams::from(material));
+}
+
+#[derive(Resource)]
+struct PreparedMaterialParams {
+    uniform: UniformBuffer<CuletMaterialParams>,
+}
+
+fn prepare_material_params(
+    mut commands: Commands,
+    params: Res<CuletMaterialParams>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let mut uniform = UniformBuffer::from(*params);
+    uniform.write_buffer(&device, &queue);
+
+    commands.insert_resource(PreparedMaterialParams { uniform });
+}
+
 #[derive(Resource)]
 pub struct ExtractedMesh {
     mesh: Option<Mesh>,
@@ -100,29 +173,38 @@ fn prepare_mesh(
     }
 }
 
+// sized to the ray-tracing camera's viewport, recreated by prepare_viewport_dims on resize.
+// texels hold the color and the primary hit distance, `history` is last frame's copy
 #[derive(Resource)]
 struct OutputTexture {
     texture: Texture,
+    history: Texture,
+    size: UVec2,
 }
-impl FromWorld for OutputTexture {
-    fn from_world(world: &mut World) -> Self {
-        let device = world.resource::<RenderDevice>();
-        let texture = device.create_texture(&TextureDescriptor {
-            label: None,
-            size: Extent3d {
-                width: 1024,
-                height: 1024,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba32Float,
-            usage: TextureUsages::all(),
-            view_formats: &[],
-        });
+impl OutputTexture {
+    fn new(device: &RenderDevice, size: UVec2) -> Self {
+        let create = |label| {
+            device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size: Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba32Float,
+                usage: TextureUsages::all(),
+                view_formats: &[],
+            })
+        };
 
-        Self { texture }
+        Self {
+            texture: create("ray tracing output"),
+            history: create("ray tracing history"),
+            size,
+        }
     }
 }
 
@@ -187,18 +269,432 @@ struct PreparedViewportDims {
 fn prepare_viewport_dims(
     mut commands: Commands,
     camera: Query<&ExtractedCamera>,
+    output_texture: Option<Res<OutputTexture>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    // the viewport size isn't known until the camera's target has been created
+    let Some(dims) = camera
+        .get_single()
+        .ok()
+        .and_then(|camera| camera.physical_viewport_size)
+    else {
+        return;
+    };
+
+    let mut uniform = UniformBuffer::from(dims);
+    uniform.write_buffer(&device, &queue);
+
+    commands.insert_resource(PreparedViewportDims { dims, uniform });
+
+    if output_texture.map_or(true, |texture| texture.size != dims) {
+        commands.insert_resource(OutputTexture::new(&device, dims));
+    }
+}
+
+// equirectangular image seen by rays leaving the stone, see environment.rs for loading one.
+// without one, escaping rays use the head-shadow lighting model
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct CuletEnvironment {
+    pub image: Option<Handle<Image>>,
+    // turns the environment about +Y in the lookup, the stone and camera stay put
+    pub rotation_degrees: f32,
+}
+
+#[derive(Clone, Copy, Default, ShaderType)]
+struct GpuEnvironment {
+    enabled: u32,
+    rotation: f32,
+}
+
+#[derive(Resource)]
+struct PreparedEnvironment {
+    uniform: UniformBuffer<GpuEnvironment>,
+}
+
+fn prepare_environment(
+    mut commands: Commands,
+    environment: Res<CuletEnvironment>,
+    images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    // the image may still be loading, keep using the lighting model until it arrives
+    let loaded = environment
+        .image
+        .as_ref()
+        .is_some_and(|image| images.get(image).is_some());
+
+    let mut uniform = UniformBuffer::from(GpuEnvironment {
+        enabled: loaded as u32,
+        rotation: environment.rotation_degrees.to_radians(),
+    });
+    uniform.write_buffer(&device, &queue);
+
+    commands.insert_resource(PreparedEnvironment { uniform });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CuletLightingModel {
+    Isometric,
+    Cosine,
+    // cosine raised to `sharpness`, softer than isometric but cheaper than an environment
+    Hemisphere { sharpness: f32 },
+}
+
+// lighting for rays that escape the stone, matching the CPU renderer's options
+#[derive(Resource, Clone, Copy, Debug, ExtractResource)]
+pub struct CuletLighting {
+    pub model: CuletLightingModel,
+    // cone around the observer blocked by their head, 0 disables
+    pub head_shadow_degrees: f32,
+}
+
+impl Default for CuletLighting {
+    fn default() -> Self {
+        Self {
+            model: CuletLightingModel::Cosine,
+            head_shadow_degrees: 10.0,
+        }
+    }
+}
+
+// every model is a power of the cosine: isometric is sharpness 0 and cosine is 1
+#[derive(Clone, Copy, Default, PartialEq, ShaderType)]
+struct GpuLighting {
+    sharpness: f32,
+    head_shadow_degrees: f32,
+}
+
+impl From<CuletLighting> for GpuLighting {
+    fn from(lighting: CuletLighting) -> Self {
+        let sharpness = match lighting.model {
+            CuletLightingModel::Isometric => 0.0,
+            CuletLightingModel::Cosine => 1.0,
+            CuletLightingModel::Hemisphere { sharpness } => sharpness,
+        };
+        Self {
+            sharpness,
+            head_shadow_degrees: lighting.head_shadow_degrees,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PreparedLighting {
+    uniform: UniformBuffer<GpuLighting>,
+}
+
+fn prepare_lighting(
+    mut commands: Commands,
+    lighting: Res<CuletLighting>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let mut uniform = UniformBuffer::from(GpuLighting::from(*lighting));
+    uniform.write_buffer(&device, &queue);
+
+    commands.insert_resource(PreparedLighting { uniform });
+}
+
+// blends each frame with the previous ones, reprojected through the primary hit distance,
+// so a still or slowly orbiting view converges to an anti-aliased image
+#[derive(Resource, Clone, Copy, Debug, ExtractResource)]
+pub struct CuletTemporalAccumulation {
+    pub enabled: bool,
+    // weight of the reprojected history where it is fully trusted
+    pub history_weight: f32,
+    // camera moves beyond these between two frames restart the accumulation
+    pub max_rotation_degrees: f32,
+    pub max_translation: f32,
+    // relative hit distance mismatch at which a reprojected pixel counts as disoccluded
+    pub depth_tolerance: f32,
+}
+
+impl Default for CuletTemporalAccumulation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            history_weight: 0.9,
+            max_rotation_degrees: 5.0,
+            max_translation: 0.25,
+            depth_tolerance: 0.02,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, ShaderType)]
+struct GpuTemporal {
+    previous_origin: Vec3,
+    previous_look_dir: Vec3,
+    previous_up: Vec3,
+    previous_fov: f32,
+    // sub-pixel offset of this frame's primary rays
+    jitter: Vec2,
+    // 0 discards the history
+    history_weight: f32,
+    depth_tolerance: f32,
+}
+
+// last frame's inputs, anything changing other than a small camera move invalidates the history
+#[derive(Resource, Default)]
+struct TemporalHistory {
+    camera: Option<CuletCameraParams>,
+    material: Option<CuletMaterialParams>,
+    lighting: Option<GpuLighting>,
+    environment: Option<(AssetId<Image>, u32)>,
+    size: UVec2,
+    frame: u32,
+}
+
+#[derive(Resource)]
+struct PreparedTemporal {
+    uniform: UniformBuffer<GpuTemporal>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_temporal(
+    mut commands: Commands,
+    mut history: ResMut<TemporalHistory>,
+    settings: Res<CuletTemporalAccumulation>,
+    camera: Res<CuletCameraParams>,
+    material: Res<CuletMaterialParams>,
+    lighting: Res<CuletLighting>,
+    environment: Res<CuletEnvironment>,
+    images: Res<RenderAssets<Image>>,
+    output_texture: Option<Res<OutputTexture>>,
     device: Res<RenderDevice>,
     queue: Res<RenderQueue>,
 ) {
-    let camera = camera.single();
+    let lighting = GpuLighting::from(*lighting);
+    // rotating the environment relights the stone, so it restarts the accumulation too
+    let environment = environment
+        .image
+        .as_ref()
+        .filter(|image| images.get(*image).is_some())
+        .map(|image| (image.id(), environment.rotation_degrees.to_bits()));
+    let size = output_texture.map_or(UVec2::ZERO, |texture| texture.size);
+
+    let camera_jumped = history.camera.map_or(true, |previous| {
+        previous
+            .look_dir
+            .angle_between(camera.look_dir)
+            .to_degrees()
+            > settings.max_rotation_degrees
+            || previous.origin.distance(camera.origin) > settings.max_translation
+            || previous.fov != camera.fov
+    });
+    let keep_history = settings.enabled
+        && !camera_jumped
+        && history.material == Some(*material)
+        && history.lighting == Some(lighting)
+        && history.environment == environment
+        && history.size == size;
+
+    let previous = history.camera.unwrap_or(*camera);
+    let frame = history.frame.wrapping_add(1);
+    *history = TemporalHistory {
+        camera: Some(*camera),
+        material: Some(*material),
+        lighting: Some(lighting),
+        environment,
+        size,
+        frame,
+    };
 
-    let mut uniform = UniformBuffer::from(camera.physical_viewport_size.unwrap());
+    // R2 sequence, so consecutive frames spread their samples evenly over each pixel
+    let jitter = if settings.enabled {
+        (Vec2::new(0.754_877_7, 0.569_840_3) * (frame % 4096) as f32).fract()
+    } else {
+        Vec2::ZERO
+    };
+
+    let mut uniform = UniformBuffer::from(GpuTemporal {
+        previous_origin: previous.origin,
+        previous_look_dir: previous.look_dir,
+        previous_up: previous.up,
+        previous_fov: previous.fov,
+        jitter,
+        history_weight: if keep_history {
+            settings.history_weight
+        } else {
+            0.0
+        },
+        depth_tolerance: settings.depth_tolerance,
+    });
     uniform.write_buffer(&device, &queue);
 
-    commands.insert_resource(PreparedViewportDims {
-        dims: camera.physical_viewport_size.unwrap(),
-        uniform,
+    commands.insert_resource(PreparedTemporal { uniform });
+}
+
+// tints pixels whose primary hit lies within `band` of `focal_distance`, to show what a lens
+// focused there would render sharp. only affects the display, not captured frames
+#[derive(Resource, Clone, Copy, Debug, ExtractResource)]
+pub struct CuletFocusPeaking {
+    pub enabled: bool,
+    // distance from the camera along each pixel's ray
+    pub focal_distance: f32,
+    pub band: f32,
+    pub color: Vec3,
+}
+
+impl Default for CuletFocusPeaking {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            focal_distance: 5.0,
+            band: 0.05,
+            color: Vec3::new(1.0, 0.0, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, ShaderType)]
+struct GpuFocusPeaking {
+    color: Vec3,
+    focal_distance: f32,
+    // 0 disables the overlay
+    band: f32,
+}
+
+#[derive(Resource)]
+struct PreparedFocusPeaking {
+    uniform: UniformBuffer<GpuFocusPeaking>,
+}
+
+fn prepare_focus_peaking(
+    mut commands: Commands,
+    focus_peaking: Res<CuletFocusPeaking>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let mut uniform = UniformBuffer::from(GpuFocusPeaking {
+        color: focus_peaking.color,
+        focal_distance: focus_peaking.focal_distance,
+        band: if focus_peaking.enabled {
+            focus_peaking.band
+        } else {
+            0.0
+        },
     });
+    uniform.write_buffer(&device, &queue);
+
+    commands.insert_resource(PreparedFocusPeaking { uniform });
+}
+
+// bumped by the app to ask for the next ray-traced frame to be written to a PNG
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct CuletFrameCapture {
+    pub requests: u32,
+}
+
+struct ReadbackBuffer {
+    buffer: Buffer,
+    size: UVec2,
+    padded_bytes_per_row: u32,
+}
+
+#[derive(Resource, Default)]
+struct FrameReadback {
+    handled: u32,
+    pending: Option<ReadbackBuffer>,
+}
+
+fn prepare_frame_readback(
+    mut readback: ResMut<FrameReadback>,
+    capture: Res<CuletFrameCapture>,
+    output_texture: Option<Res<OutputTexture>>,
+    device: Res<RenderDevice>,
+) {
+    if capture.requests == readback.handled {
+        return;
+    }
+    let Some(output_texture) = output_texture else {
+        return;
+    };
+    readback.handled = capture.requests;
+
+    // Rgba32Float texels, with rows padded to the copy alignment
+    let size = output_texture.texture.size();
+    let padded_bytes_per_row = RenderDevice::align_copy_bytes_per_row(size.width as usize * 16);
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("frame capture buffer"),
+        size: (padded_bytes_per_row * size.height as usize) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    readback.pending = Some(ReadbackBuffer {
+        buffer,
+        size: UVec2::new(size.width, size.height),
+        padded_bytes_per_row: padded_bytes_per_row as u32,
+    });
+}
+
+// runs after the frame has been submitted, so the copy recorded by CuletNode is complete
+fn save_frame_readback(mut readback: ResMut<FrameReadback>, device: Res<RenderDevice>) {
+    let Some(ReadbackBuffer {
+        buffer,
+        size,
+        padded_bytes_per_row,
+    }) = readback.pending.take()
+    else {
+        return;
+    };
+
+    let (sender, receiver) = channel();
+    buffer.slice(..).map_async(MapMode::Read, move |r| {
+        let _ = sender.send(r);
+    });
+    device.poll(Maintain::Wait);
+    if !matches!(receiver.recv(), Ok(Ok(()))) {
+        error!("failed to read back the ray-traced frame");
+        return;
+    }
+
+    let mut pixels = Vec::with_capacity((size.x * size.y * 4) as usize);
+    {
+        let view = buffer.slice(..).get_mapped_range();
+        for row in view.chunks_exact(padded_bytes_per_row as usize) {
+            let texels: &[f32] = bytemuck::cast_slice(&row[..size.x as usize * 16]);
+            for texel in texels.chunks_exact(4) {
+                // the blit target is sRGB, so encode the same way for the file
+                let linear = culet_lib::glam::Vec3::new(texel[0], texel[1], texel[2]);
+                let srgb = (linear_to_srgb(linear) * 255.0).round();
+                pixels.extend(srgb.to_array().map(|c| c as u8));
+                pixels.push(255);
+            }
+        }
+    }
+    buffer.unmap();
+
+    let path = format!("culet_frame_{}.png", readback.handled);
+    match image::RgbaImage::from_raw(size.x, size.y, pixels).map(|image| image.save(&path)) {
+        Some(Ok(())) => info!("saved ray-traced frame to {path}"),
+        Some(Err(e)) => error!("failed to save {path}: {e}"),
+        None => error!("frame capture size mismatch"),
+    }
+}
+
+pub const SAMPLES_PER_SECOND: DiagnosticPath =
+    DiagnosticPath::const_new("culet/samples_per_second");
+
+// the compute shader traces one primary sample per viewport pixel each frame
+fn measure_samples_per_second(
+    mut diagnostics: Diagnostics,
+    time: Res<Time>,
+    camera: Query<&Camera, With<CuletCamera>>,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    if !camera.is_active || time.delta_seconds_f64() == 0.0 {
+        return;
+    }
+    if let Some(size) = camera.physical_viewport_size() {
+        let samples = size.x as f64 * size.y as f64;
+        diagnostics.add_measurement(&SAMPLES_PER_SECOND, || samples / time.delta_seconds_f64());
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, RenderSubGraph)]
@@ -230,7 +726,10 @@ impl ViewNode for CuletNode {
             .get_compute_pipeline(culet_pipeline.compute_pipeline_id)
             .unwrap();
 
-        let output_texture = world.resource::<OutputTexture>();
+        // nothing to trace into until the viewport size is known
+        let Some(output_texture) = world.get_resource::<OutputTexture>() else {
+            return Ok(());
+        };
         let output_texture_view = output_texture.texture.create_view(&TextureViewDescriptor {
             label: Some("compute shader output texture"),
             format: Some(TextureFormat::Rgba32Float),
@@ -244,6 +743,22 @@ impl ViewNode for CuletNode {
 
         let prepared_mesh = world.resource::<PreparedMesh>();
         let camera_params = world.resource::<PreparedCameraParams>();
+        let prepared_environment = world.resource::<PreparedEnvironment>();
+        let material_params = world.resource::<PreparedMaterialParams>();
+        let lighting = world.resource::<PreparedLighting>();
+        let temporal = world.resource::<PreparedTemporal>();
+        let history_view = output_texture
+            .history
+            .create_view(&TextureViewDescriptor::default());
+
+        // an unloaded environment binds the fallback image, which the shader then ignores
+        let environment_view = world
+            .resource::<CuletEnvironment>()
+            .image
+            .as_ref()
+            .and_then(|image| world.resource::<RenderAssets<Image>>().get(image))
+            .map(|image| &image.texture_view)
+            .unwrap_or(&world.resource::<FallbackImage>().d2.texture_view);
 
         let compute_bind_group = render_context.render_device().create_bind_group(
             None,
@@ -255,6 +770,12 @@ impl ViewNode for CuletNode {
                 prepared_mesh.bvh_nodes.binding().unwrap(),
                 camera_params.uniform.binding().unwrap(),
                 &output_texture_view,
+                environment_view,
+                prepared_environment.uniform.binding().unwrap(),
+                material_params.uniform.binding().unwrap(),
+                lighting.uniform.binding().unwrap(),
+                &history_view,
+                temporal.uniform.binding().unwrap(),
             )),
         );
 
@@ -263,6 +784,7 @@ impl ViewNode for CuletNode {
             .unwrap();
 
         let viewport_dims = world.resource::<PreparedViewportDims>();
+        let focus_peaking = world.resource::<PreparedFocusPeaking>();
 
         let render_bind_group = render_context.render_device().create_bind_group(
             None,
@@ -270,6 +792,7 @@ impl ViewNode for CuletNode {
             &BindGroupEntries::sequential((
                 &output_texture_view,
                 viewport_dims.uniform.binding().unwrap(),
+                focus_peaking.uniform.binding().unwrap(),
             )),
         );
 
@@ -288,6 +811,36 @@ impl ViewNode for CuletNode {
         );
         drop(compute_pass);
 
+        // keep this frame around for the next one to reproject
+        command_encoder.copy_texture_to_texture(
+            output_texture.texture.as_image_copy(),
+            output_texture.history.as_image_copy(),
+            Extent3d {
+                width: output_texture.size.x,
+                height: output_texture.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        if let Some(readback) = &world.resource::<FrameReadback>().pending {
+            command_encoder.copy_texture_to_buffer(
+                output_texture.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &readback.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(readback.padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: readback.size.x,
+                    height: readback.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
         let color_attachment_load_op = if let Some(camera) = camera {
             match camera.output_mode {
                 CameraOutputMode::Write {
@@ -345,6 +898,12 @@ impl FromWorld for CuletPipeline {
                     storage_buffer_read_only::<Vec<BvhNode>>(false), // BVH nodes
                     uniform_buffer::<CuletCameraParams>(false),
                     texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::ReadWrite), // output texture
+                    texture_2d(TextureSampleType::Float { filterable: false }), // environment
+                    uniform_buffer::<GpuEnvironment>(false),                    // environment
+                    uniform_buffer::<CuletMaterialParams>(false),
+                    uniform_buffer::<GpuLighting>(false),
+                    texture_2d(TextureSampleType::Float { filterable: false }), // history
+                    uniform_buffer::<GpuTemporal>(false),
                 ),
             ),
         );
@@ -355,6 +914,7 @@ impl FromWorld for CuletPipeline {
                 (
                     texture_2d(TextureSampleType::Float { filterable: false }),
                     uniform_buffer::<UVec2>(false),
+                    uniform_buffer::<GpuFocusPeaking>(false),
                 ),
             ),
         );
@@ -422,6 +982,21 @@ pub struct CuletPlugin;
 
 impl Plugin for CuletPlugin {
     fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(SAMPLES_PER_SECOND).with_suffix(" samples/s"))
+            .add_systems(Update, measure_samples_per_second);
+        app.init_resource::<CuletEnvironment>()
+            .init_resource::<CuletFrameCapture>()
+            .init_resource::<CuletLighting>()
+            .init_resource::<CuletTemporalAccumulation>()
+            .init_resource::<CuletFocusPeaking>()
+            .add_plugins((
+                ExtractResourcePlugin::<CuletEnvironment>::default(),
+                ExtractResourcePlugin::<CuletFrameCapture>::default(),
+                ExtractResourcePlugin::<CuletLighting>::default(),
+                ExtractResourcePlugin::<CuletTemporalAccumulation>::default(),
+                ExtractResourcePlugin::<CuletFocusPeaking>::default(),
+            ));
+
         let render_app = app.get_sub_app_mut(RenderApp).unwrap();
 
         render_app.add_systems(
@@ -429,6 +1004,7 @@ impl Plugin for CuletPlugin {
             (
                 extract_mesh.in_set(RenderSet::ExtractCommands),
                 extract_camera_params.in_set(RenderSet::ExtractCommands),
+                extract_material_params.in_set(RenderSet::ExtractCommands),
             ),
         );
         render_app.add_systems(
@@ -436,7 +1012,15 @@ impl Plugin for CuletPlugin {
             (
                 prepare_mesh.in_set(RenderSet::Prepare),
                 prepare_camera_params.in_set(RenderSet::Prepare),
+                prepare_environment.in_set(RenderSet::Prepare),
+                prepare_material_params.in_set(RenderSet::Prepare),
+                prepare_lighting.in_set(RenderSet::Prepare),
+                prepare_focus_peaking.in_set(RenderSet::Prepare),
                 prepare_viewport_dims.in_set(RenderSet::PrepareResources),
+                // after the output texture may have been resized
+                prepare_frame_readback.in_set(RenderSet::PrepareBindGroups),
+                prepare_temporal.in_set(RenderSet::PrepareBindGroups),
+                save_frame_readback.in_set(RenderSet::Cleanup),
             ),
         );
 
@@ -452,6 +1036,8 @@ impl Plugin for CuletPlugin {
         let render_app = app.get_sub_app_mut(RenderApp).unwrap();
 
         render_app.init_resource::<CuletPipeline>();
-        render_app.init_resource::<OutputTexture>();
+        render_app.init_resource::<FrameReadback>();
+        render_app.init_resource::<TemporalHistory>();
+        render_app.init_resource::<CuletMaterialParams>();
     }
 }
@@ -0,0 +1,90 @@
+// compares primary ray throughput traced one ray at a time against PACKET_TILE square packets
+//
+//     cargo run --release --example primary_rays -- <mesh.stl>
+use std::time::Instant;
+
+use culet_lib::{
+    camera::Camera,
+    glam::{vec3, Vec3},
+    hittable::Hittable,
+    mesh::Mesh,
+    ray::Ray,
+    render::PACKET_TILE,
+    scene::Scene,
+};
+
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 1024;
+const ROUNDS: usize = 5;
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: primary_rays <mesh.stl>");
+        std::process::exit(2);
+    };
+    let mesh = Mesh::load_from_stl(Vec3::ZERO, path);
+    let center = mesh.bounding_box().center();
+    let size = (mesh.bounding_box().max() - mesh.bounding_box().min()).length();
+    let scene = Scene::new(vec![mesh]);
+    let camera = Camera::default()
+        .position(center + vec3(0.0, 0.0, size.max(1.0) * 2.0))
+        .look_at(center)
+        .fov(45.0)
+        .aspect_ratio(WIDTH as f32 / HEIGHT as f32);
+    let min_distance = scene.shadow_bias();
+
+    let ray = |x: usize, y: usize| {
+        camera.ray(
+            (x as f32 + 0.5) / WIDTH as f32,
+            (y as f32 + 0.5) / HEIGHT as f32,
+        )
+    };
+    let tiles: Vec<Vec<Ray>> = (0..HEIGHT)
+        .step_by(PACKET_TILE)
+        .flat_map(|y0| (0..WIDTH).step_by(PACKET_TILE).map(move |x0| (x0, y0)))
+        .map(|(x0, y0)| {
+            (y0..(y0 + PACKET_TILE).min(HEIGHT))
+                .flat_map(|y| (x0..(x0 + PACKET_TILE).min(WIDTH)).map(move |x| (x, y)))
+                .map(|(x, y)| ray(x, y))
+                .collect()
+        })
+        .collect();
+    let rays = (ROUNDS * WIDTH * HEIGHT) as f64;
+
+    let start = Instant::now();
+    let mut single_hits = 0;
+    for _ in 0..ROUNDS {
+        for tile in tiles.iter() {
+            single_hits += tile
+                .iter()
+                .filter(|ray| {
+                    scene
+                        .hit_point_within(ray, min_distance, f32::INFINITY)
+                        .is_some()
+                })
+                .count();
+        }
+    }
+    let single = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    let mut packet_hits = 0;
+    for _ in 0..ROUNDS {
+        for tile in tiles.iter() {
+            packet_hits += scene
+                .hit_packet_within(tile, min_distance, f32::INFINITY)
+                .iter()
+                .filter(|hit| hit.is_some())
+                .count();
+        }
+    }
+    let packet = start.elapsed().as_secs_f64();
+
+    assert_eq!(
+        single_hits, packet_hits,
+        "packets must hit what single rays hit"
+    );
+    println!("single rays: {:.2} Mrays/s", rays / single / 1e6);
+    println!("packets:     {:.2} Mrays/s", rays / packet / 1e6);
+    println!("speedup:     {:.2}x", single / packet);
+}
@@ -1,6 +1,8 @@
 use bytemuck::{Pod, Zeroable};
 use glam::*;
 
+use crate::ray::Ray;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Camera {
@@ -63,6 +65,14 @@ impl Camera {
             _pad_3: 0.0,
         }
     }
+    // photographic spec: stand at `from` and aim at `to`, focal length 1
+    pub fn look_from_to(from: Vec3, to: Vec3, up: Vec3, fov_h: f32, aspect_ratio: f32) -> Self {
+        assert!(
+            from.distance(to) > f32::EPSILON,
+            "Camera position and observation point must not coincide"
+        );
+        Self::new(from, to - from, up, fov_h, aspect_ratio, 1.0)
+    }
     pub fn viewport(&self) -> (Vec3, Vec3, Vec3) {
         let horizontal_distance = self.focal_length * (self.fov_h / 2.0).to_radians().tan();
         let vertical_distance = horizontal_distance / self.aspect_ratio;
@@ -86,18 +96,26 @@ impl Camera {
             up * -2.0 * vertical_distance,
         )
     }
+    // primary ray through normalized screen coordinates, (0, 0) is the top left corner of
+    // the viewport and (1, 1) the bottom right
+    pub fn ray(&self, u: f32, v: f32) -> Ray {
+        let (top_left, viewport_width, viewport_height) = self.viewport();
+        let point = top_left + u * viewport_width + v * viewport_height;
+        Ray::new(self.position, point - self.position)
+    }
     pub fn position(mut self, position: Vec3) -> Self {
         self.position = position;
         self
     }
+    // the point only sets the direction, it can be nearer than the focal length
     pub fn look_at(mut self, point: Vec3) -> Self {
-        self.look_dir = point - self.position;
+        let look_dir = point - self.position;
         assert!(
-            self.look_dir.length() > self.focal_length,
-            "Camera observation point must be further than the focal distance"
+            look_dir.length() > f32::EPSILON,
+            "Camera position and observation point must not coincide"
         );
 
-        self.look_dir = self.look_dir.normalize();
+        self.look_dir = look_dir.normalize();
         self
     }
     pub fn fov(mut self, fov: f32) -> Self {
@@ -111,4 +129,12 @@ impl Camera {
     pub fn look_dir(&self) -> Vec3 {
         self.look_dir
     }
+    pub fn horizontal_fov(&self) -> f32 {
+        self.fov_h
+    }
+    // vertical field of view in degrees, derived from the horizontal fov and aspect ratio
+    pub fn vertical_fov(&self) -> f32 {
+        let half_width = (self.fov_h / 2.0).to_radians().tan();
+        2.0 * (half_width / self.aspect_ratio).atan().to_degrees()
+    }
 }
@@ -0,0 +1,156 @@
+// progress of a blocking render saved to disk, so a crashed or time-limited render can pick
+// up where it stopped instead of starting over
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use glam::Vec3;
+
+const MAGIC: &[u8; 8] = b"CULETCKP";
+const VERSION: u32 = 2;
+// magic, version, width and height
+const HEADER_BYTES: u64 = 20;
+// rgb sum, sample count and primary hit count
+const PIXEL_BYTES: u64 = 20;
+
+// per-pixel sums of the samples traced so far, how many there were and how many of their
+// primary rays hit geometry, row-major
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    width: usize,
+    height: usize,
+    sums: Vec<Vec3>,
+    samples: Vec<u32>,
+    hits: Vec<u32>,
+}
+
+impl Checkpoint {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            sums: vec![Vec3::ZERO; width * height],
+            samples: vec![0; width * height],
+            hits: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn samples(&self, pixel: usize) -> u32 {
+        self.samples[pixel]
+    }
+
+    pub fn sum(&self, pixel: usize) -> Vec3 {
+        self.sums[pixel]
+    }
+
+    pub fn hits(&self, pixel: usize) -> u32 {
+        self.hits[pixel]
+    }
+
+    // mean of the pixel's samples, black before it has any
+    pub fn color(&self, pixel: usize) -> Vec3 {
+        match self.samples[pixel] {
+            0 => Vec3::ZERO,
+            n => self.sums[pixel] / n as f32,
+        }
+    }
+
+    // share of the pixel's primary rays that hit geometry, 0 before it has any
+    pub fn alpha(&self, pixel: usize) -> f32 {
+        match self.samples[pixel] {
+            0 => 0.0,
+            n => self.hits[pixel] as f32 / n as f32,
+        }
+    }
+
+    // `color` and `alpha` are over all `samples` of the pixel, including any it already had
+    pub fn record(&mut self, pixel: usize, color: Vec3, alpha: f32, samples: u32) {
+        self.sums[pixel] = color * samples as f32;
+        self.samples[pixel] = samples;
+        self.hits[pixel] = (alpha * samples as f32).round() as u32;
+    }
+
+    // little endian: magic, version, width, height, then rgb sum, sample count and primary hit
+    // count per pixel.
+    // written to a sibling file and renamed over `path` so a crash mid-write keeps the last one
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        {
+            let mut file = BufWriter::new(File::create(&temp_path)?);
+            file.write_all(MAGIC)?;
+            for value in [VERSION, self.width as u32, self.height as u32] {
+                file.write_all(&value.to_le_bytes())?;
+            }
+            for pixel in 0..self.sums.len() {
+                for channel in self.sums[pixel].to_array() {
+                    file.write_all(&channel.to_le_bytes())?;
+                }
+                file.write_all(&self.samples[pixel].to_le_bytes())?;
+                file.write_all(&self.hits[pixel].to_le_bytes())?;
+            }
+            file.flush()?;
+        }
+        std::fs::rename(&temp_path, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a render checkpoint"));
+        }
+        let version = read_u32(&mut file)?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported checkpoint version {version}"
+            )));
+        }
+        let (width, height) = (read_u32(&mut file)?, read_u32(&mut file)?);
+        // a corrupt header mustn't get to allocate whatever size it claims
+        let expected = (width as u64 * height as u64)
+            .checked_mul(PIXEL_BYTES)
+            .and_then(|bytes| bytes.checked_add(HEADER_BYTES));
+        if expected != Some(length) {
+            return Err(invalid_data(format!(
+                "checkpoint header says {width}x{height} but the file is {length} bytes"
+            )));
+        }
+        let (width, height) = (width as usize, height as usize);
+
+        let mut checkpoint = Self::new(width, height);
+        for pixel in 0..width * height {
+            let mut sum = [0.0; 3];
+            for channel in sum.iter_mut() {
+                *channel = f32::from_bits(read_u32(&mut file)?);
+            }
+            checkpoint.sums[pixel] = Vec3::from_array(sum);
+            checkpoint.samples[pixel] = read_u32(&mut file)?;
+            checkpoint.hits[pixel] = read_u32(&mut file)?;
+        }
+        Ok(checkpoint)
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
@@ -0,0 +1,131 @@
+use glam::{vec3, Vec3};
+
+pub const WAVELENGTH_MIN: f32 = 380.0;
+pub const WAVELENGTH_MAX: f32 = 780.0;
+const WAVELENGTH_STEP: f32 = 10.0;
+
+// CIE 1931 2° standard observer color-matching functions, 380nm to 780nm in 10nm steps
+const CIE_1931: [[f32; 3]; 41] = [
+    [0.001368, 0.000039, 0.006450],
+    [0.004243, 0.000120, 0.020050],
+    [0.014310, 0.000396, 0.067850],
+    [0.043510, 0.001210, 0.207400],
+    [0.134380, 0.004000, 0.645600],
+    [0.283900, 0.011600, 1.385600],
+    [0.348280, 0.023000, 1.747060],
+    [0.336200, 0.038000, 1.772110],
+    [0.290800, 0.060000, 1.669200],
+    [0.195360, 0.090980, 1.287640],
+    [0.095640, 0.139020, 0.812950],
+    [0.032010, 0.208020, 0.465180],
+    [0.004900, 0.323000, 0.272000],
+    [0.009300, 0.503000, 0.158200],
+    [0.063270, 0.710000, 0.078250],
+    [0.165500, 0.862000, 0.042160],
+    [0.290400, 0.954000, 0.020300],
+    [0.433450, 0.994950, 0.008750],
+    [0.594500, 0.995000, 0.003900],
+    [0.762100, 0.952000, 0.002100],
+    [0.916300, 0.870000, 0.001650],
+    [1.026300, 0.757000, 0.001100],
+    [1.062200, 0.631000, 0.000800],
+    [1.002600, 0.503000, 0.000340],
+    [0.854450, 0.381000, 0.000190],
+    [0.642400, 0.265000, 0.000050],
+    [0.447900, 0.175000, 0.000020],
+    [0.283500, 0.107000, 0.000000],
+    [0.164900, 0.061000, 0.000000],
+    [0.087400, 0.032000, 0.000000],
+    [0.046770, 0.017000, 0.000000],
+    [0.022700, 0.008210, 0.000000],
+    [0.011359, 0.004102, 0.000000],
+    [0.005790, 0.002091, 0.000000],
+    [0.002899, 0.001047, 0.000000],
+    [0.001440, 0.000520, 0.000000],
+    [0.000690, 0.000249, 0.000000],
+    [0.000332, 0.000120, 0.000000],
+    [0.000166, 0.000060, 0.000000],
+    [0.000083, 0.000030, 0.000000],
+    [0.000042, 0.000015, 0.000000],
+];
+
+// integral of the y-bar color-matching function over the tabulated range
+const CIE_Y_INTEGRAL: f32 = 106.857;
+
+// linearly interpolated CIE XYZ response to a single wavelength, zero outside the visible range
+pub fn wavelength_to_xyz(nm: f32) -> Vec3 {
+    if !(WAVELENGTH_MIN..=WAVELENGTH_MAX).contains(&nm) {
+        return Vec3::ZERO;
+    }
+    let position = (nm - WAVELENGTH_MIN) / WAVELENGTH_STEP;
+    let index = (position as usize).min(CIE_1931.len() - 2);
+    let t = position - index as f32;
+
+    let [x0, y0, z0] = CIE_1931[index];
+    let [x1, y1, z1] = CIE_1931[index + 1];
+    vec3(x0, y0, z0).lerp(vec3(x1, y1, z1), t)
+}
+
+// CIE XYZ to linear sRGB (D65 white point)
+pub fn xyz_to_srgb_linear(xyz: Vec3) -> Vec3 {
+    vec3(
+        3.240_454 * xyz.x - 1.537_139 * xyz.y - 0.498_531 * xyz.z,
+        -0.969_266 * xyz.x + 1.876_011 * xyz.y + 0.041_556 * xyz.z,
+        0.055_643 * xyz.x - 0.204_026 * xyz.y + 1.057_225 * xyz.z,
+    )
+}
+
+// piecewise sRGB transfer function, linear near black and a 1/2.4 power curve above.
+// components are clamped to [0, 1]
+pub fn linear_to_srgb(color: Vec3) -> Vec3 {
+    Vec3::from_array(color.to_array().map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(2.4f32.recip()) - 0.055
+        }
+    }))
+}
+
+// inverse of `linear_to_srgb`, for decoding 8-bit images into linear color
+pub fn srgb_to_linear(color: Vec3) -> Vec3 {
+    Vec3::from_array(color.to_array().map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.040_45 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }))
+}
+
+// accumulates radiance samples at uniformly chosen wavelengths into a single pixel color
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpectralAccumulator {
+    xyz: Vec3,
+    samples: u32,
+}
+
+impl SpectralAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, nm: f32, radiance: f32) {
+        self.xyz += wavelength_to_xyz(nm) * radiance;
+        self.samples += 1;
+    }
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+    pub fn xyz(&self) -> Vec3 {
+        if self.samples == 0 {
+            return Vec3::ZERO;
+        }
+        // Monte Carlo estimate of the XYZ integral, normalised so a flat unit spectrum has Y = 1
+        self.xyz / self.samples as f32 * (WAVELENGTH_MAX - WAVELENGTH_MIN) / CIE_Y_INTEGRAL
+    }
+    pub fn to_srgb_linear(self) -> Vec3 {
+        xyz_to_srgb_linear(self.xyz())
+    }
+}
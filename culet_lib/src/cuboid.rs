@@ -0,0 +1,66 @@
+use glam::*;
+
+use crate::{
+    hittable::{HitInfo, Hittable},
+    material::Material,
+    ray::Ray,
+};
+
+// axis-aligned box that can be shaded, unlike a mesh's BoundingBox
+#[derive(Copy, Clone, Debug)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: Material,
+}
+
+impl Cuboid {
+    // any two opposite corners
+    pub fn new(a: Vec3, b: Vec3, material: Material) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+            material,
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit_point(&self, ray: &Ray, min_distance: f32) -> Option<HitInfo> {
+        if ray.is_degenerate() {
+            return None;
+        }
+        // slab method, the ray is inside the box between the last slab entered and the first
+        // slab left
+        let inv_dir = ray.direction().recip();
+        let t0 = (self.min - ray.origin()) * inv_dir;
+        let t1 = (self.max - ray.origin()) * inv_dir;
+        let (near, far) = (t0.min(t1), t0.max(t1));
+        let (t_enter, t_exit) = (near.max_element(), far.min_element());
+        if t_exit < t_enter {
+            return None;
+        }
+
+        // entering through a face, or leaving through one when the ray starts inside
+        let (t, slabs, sign) = if t_enter > min_distance {
+            (t_enter, near, -1.0)
+        } else if t_exit > min_distance {
+            (t_exit, far, 1.0)
+        } else {
+            return None;
+        };
+        let axis = (0..3).find(|&i| slabs[i] == t).unwrap_or(0);
+        let mut normal = Vec3::ZERO;
+        normal[axis] = sign * ray.direction()[axis].signum();
+
+        Some(HitInfo {
+            position: ray.origin() + t * ray.direction(),
+            normal,
+            ray_distance: t,
+            front_face: ray.direction().dot(normal) < 0.0,
+            material: self.material,
+            bary: Vec2::ZERO,
+            uv: None,
+        })
+    }
+}
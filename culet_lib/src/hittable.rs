@@ -7,6 +7,12 @@ pub trait Hittable {
     fn hit_by(&self, ray: &Ray, min_distance: f32) -> bool {
         self.hit_point(ray, min_distance).is_some()
     }
+    // closest hit no further than `max_distance`, implementors can override this to skip
+    // distant geometry entirely
+    fn hit_point_within(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitInfo> {
+        self.hit_point(ray, min_distance)
+            .filter(|info| info.ray_distance <= max_distance)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -16,4 +22,8 @@ pub struct HitInfo {
     pub ray_distance: f32,
     pub front_face: bool,
     pub material: Material,
+    // barycentric (u, v) of the hit, weighting the triangle's second and third vertices
+    pub bary: Vec2,
+    // interpolated texture coordinates, if the surface has any
+    pub uv: Option<Vec2>,
 }
@@ -1,10 +1,22 @@
 pub mod camera;
+pub mod checkpoint;
+pub mod color;
+pub mod cuboid;
 pub mod hittable;
 pub mod material;
 pub mod mesh;
+pub mod montage;
+mod obj;
+#[cfg(feature = "caustics")]
+pub mod photon_map;
+mod ply;
+pub mod post;
 pub mod ray;
 pub mod render;
+pub mod sampler;
 pub mod scene;
+pub mod sphere;
+pub mod texture;
 pub mod wgpu;
 
-pub use glam;
\ No newline at end of file
+pub use glam;
@@ -4,19 +4,66 @@ pub const DEFAULT_GEM_COLOR: Vec3 = Vec3::new(0.0, 0.0, 0.0);
 pub const DEFAULT_GEM_RI: f32 = 1.54;
 pub const DEFAULT_GEM_DISPERSION: f32 = 0.008;
 
+// refractive indices of common stones, for picking a gem by name
+pub const GEM_RI_PRESETS: [(&str, f32); 5] = [
+    ("Diamond", 2.417),
+    ("Sapphire", 1.77),
+    ("Quartz", 1.544),
+    ("CZ", 2.15),
+    ("Glass", 1.52),
+];
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Material {
     Refractive {
         color: Vec3,
         refractive_index: f32,
         dispersion: f32,
+        // glow per unit distance travelled inside the stone while the UV light is on
+        fluorescence: Vec3,
+        // scattering events per unit distance inside the stone, 0 for a clean stone
+        inclusion_density: f32,
+        // fraction of the light each inclusion scatters rather than absorbs
+        inclusion_color: Vec3,
+    },
+    // refractive stone with an interference coating of `film_ri` on its outside. the base is
+    // always a stone, materials are Copy and stored per triangle so one can't box another
+    ThinFilm {
+        color: Vec3,
+        refractive_index: f32,
+        thickness_nm: f32,
+        film_ri: f32,
     },
+    // Oren-Nayar surface, `roughness` is the facet slope deviation in radians and 0 is Lambertian
     Diffuse {
         color: Vec3,
+        roughness: f32,
+    },
+    // opaque Lambertian base under a Ward anisotropic specular lobe, like the sheen of aligned
+    // rutile needles behind asterism. `tangent` is the needle direction, projected onto each
+    // surface it shades, and the roughnesses are the lobe's slope spread along and across it
+    Anisotropic {
+        color: Vec3,
+        specular: Vec3,
+        tangent: Vec3,
+        roughness_along: f32,
+        roughness_across: f32,
     },
     Light {
         color: Vec3,
     },
+    Checker {
+        color_a: Vec3,
+        color_b: Vec3,
+        scale: f32,
+    },
+    // diffuse surface sampling a texture from the scene, `color` is used when a triangle has no
+    // UVs. the texture is an index into Scene::add_texture's table rather than the image
+    // itself, an Arc would stop materials being Copy
+    Textured {
+        texture: usize,
+        color: Vec3,
+    },
 }
 
 impl Default for Material {
@@ -37,17 +84,96 @@ impl Material {
             color: DEFAULT_GEM_COLOR,
             refractive_index: DEFAULT_GEM_RI,
             dispersion: DEFAULT_GEM_DISPERSION,
+            fluorescence: Vec3::ZERO,
+            inclusion_density: 0.0,
+            inclusion_color: Vec3::ONE,
+        }
+    }
+    pub fn diffuse(color: Vec3) -> Self {
+        Self::Diffuse {
+            color,
+            roughness: 0.0,
         }
     }
     pub fn color(&self) -> Vec3 {
+        match *self {
+            Self::Refractive { color, .. }
+            | Self::ThinFilm { color, .. }
+            | Self::Diffuse { color, .. }
+            | Self::Anisotropic { color, .. }
+            | Self::Light { color }
+            | Self::Textured { color, .. } => color,
+            Self::Checker { color_a, .. } => color_a,
+        }
+    }
+    // returns whether the color changed, checker materials have no single color to set
+    pub fn set_color(&mut self, new_color: Vec3) -> bool {
+        match self {
+            Self::Refractive { color, .. }
+            | Self::ThinFilm { color, .. }
+            | Self::Diffuse { color, .. }
+            | Self::Anisotropic { color, .. }
+            | Self::Light { color }
+            | Self::Textured { color, .. } => {
+                let changed = *color != new_color;
+                *color = new_color;
+                changed
+            }
+            Self::Checker { .. } => false,
+        }
+    }
+    // returns whether the refractive index changed, no-op for non-refractive materials
+    pub fn set_refractive_index(&mut self, new_ri: f32) -> bool {
+        match self {
+            Self::Refractive {
+                refractive_index, ..
+            }
+            | Self::ThinFilm {
+                refractive_index, ..
+            } => {
+                let changed = *refractive_index != new_ri;
+                *refractive_index = new_ri;
+                changed
+            }
+            _ => false,
+        }
+    }
+    // UV-excited emission per unit distance inside the material, zero for everything but
+    // refractive stones
+    pub fn fluorescence(&self) -> Vec3 {
+        match *self {
+            Self::Refractive { fluorescence, .. } => fluorescence,
+            _ => Vec3::ZERO,
+        }
+    }
+    // density and color of the scattering inclusions inside the material, no inclusions for
+    // everything but refractive stones
+    pub fn inclusions(&self) -> (f32, Vec3) {
         match *self {
             Self::Refractive {
-                color,
-                refractive_index: _,
-                dispersion: _,
+                inclusion_density,
+                inclusion_color,
+                ..
+            } => (inclusion_density, inclusion_color),
+            _ => (0.0, Vec3::ONE),
+        }
+    }
+    // surface color at a world-space position, for materials with procedural patterns
+    pub fn color_at(&self, position: Vec3) -> Vec3 {
+        match *self {
+            Self::Checker {
+                color_a,
+                color_b,
+                scale,
+            } => {
+                let cell = (position / scale).floor();
+                if (cell.x + cell.y + cell.z).rem_euclid(2.0) < 1.0 {
+                    color_a
+                } else {
+                    color_b
+                }
             }
-            | Self::Diffuse { color }
-            | Self::Light { color } => color,
+            _ => self.color(),
         }
     }
 }
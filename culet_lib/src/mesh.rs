@@ -1,5 +1,8 @@
 use std::{
-    fs::OpenOptions,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{File, OpenOptions},
+    io::BufReader,
     ops::{Index, Range},
     path::Path,
 };
@@ -11,6 +14,7 @@ use stl_io::create_stl_reader;
 use crate::{
     hittable::{HitInfo, Hittable},
     material::Material,
+    obj, ply,
 };
 
 #[derive(Copy, Clone, Debug)]
@@ -18,6 +22,11 @@ pub struct Triangle {
     points: [Vec3; 3],
     normal: Vec3,
     material: Material,
+    // single-sided opaque triangles can't be hit from behind
+    double_sided: bool,
+    uvs: Option<[Vec2; 3]>,
+    // per-vertex colors, which take the place of a Diffuse material's color
+    colors: Option<[Vec3; 3]>,
 }
 
 impl Index<usize> for Triangle {
@@ -30,13 +39,15 @@ impl Index<usize> for Triangle {
 
 impl Triangle {
     pub fn new(p1: Vec3, p2: Vec3, p3: Vec3) -> Self {
-        let out = Self {
+        Self {
             points: [p1, p2, p3],
-            normal: (p2 - p1).cross(p3 - p1).normalize(),
+            // zero for degenerate triangles, which validate and repair pick out
+            normal: (p2 - p1).cross(p3 - p1).normalize_or_zero(),
             material: Material::default(),
-        };
-        dbg!(out.normal);
-        out
+            double_sided: true,
+            uvs: None,
+            colors: None,
+        }
     }
     pub fn translate(&mut self, vector: Vec3) {
         self.points.iter_mut().for_each(|p| *p += vector)
@@ -45,6 +56,44 @@ impl Triangle {
         self.material = material;
         self
     }
+    pub fn with_uvs(mut self, uvs: [Vec2; 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+    pub fn with_vertex_colors(mut self, colors: [Vec3; 3]) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+    pub fn with_double_sided(mut self, double_sided: bool) -> Self {
+        self.double_sided = double_sided;
+        self
+    }
+    // refractive and coated triangles are always double sided, light has to be able to leave
+    // the medium
+    pub fn is_double_sided(&self) -> bool {
+        self.double_sided
+            || matches!(
+                self.material,
+                Material::Refractive { .. } | Material::ThinFilm { .. }
+            )
+    }
+    pub fn points(&self) -> [Vec3; 3] {
+        self.points
+    }
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+    pub fn material(&self) -> Material {
+        self.material
+    }
+    pub fn area(&self) -> f32 {
+        0.5 * (self[1] - self[0]).cross(self[2] - self[0]).length()
+    }
+    // map two uniform random numbers in [0, 1) to a uniformly distributed point on the triangle
+    pub fn sample_point(&self, u: f32, v: f32) -> Vec3 {
+        let su = u.sqrt();
+        self[0] * (1.0 - su) + self[1] * (su * (1.0 - v)) + self[2] * (su * v)
+    }
 }
 
 impl From<stl_io::Triangle> for Triangle {
@@ -65,11 +114,14 @@ impl From<stl_io::Triangle> for Triangle {
             value.vertices[2][2],
         );
         // let normal = Vec3::new(value.normal[0], value.normal[1], value.normal[2]);
-        let normal = (p2 - p1).cross(p3 - p1).normalize();
+        let normal = (p2 - p1).cross(p3 - p1).normalize_or_zero();
         Self {
             points: [p1, p2, p3],
             normal,
             material: Material::default(),
+            double_sided: true,
+            uvs: None,
+            colors: None,
         }
     }
 }
@@ -151,6 +203,11 @@ impl Hittable for Triangle {
             return None;
         }
 
+        // determinant is negative when the ray hits the back face
+        if determinant < 0.0 && !self.is_double_sided() {
+            return None;
+        }
+
         let inv_det = 1.0 / determinant;
         let tvec = ray.origin() - self[0];
         let u = tvec.dot(pvec) * inv_det;
@@ -171,6 +228,10 @@ impl Hittable for Triangle {
         let t = edge02.dot(qvec) * inv_det;
 
         if t > min_distance {
+            let mut material = self.material;
+            if let (Some([c0, c1, c2]), Material::Diffuse { .. }) = (self.colors, material) {
+                material.set_color(c0 * (1.0 - u - v) + c1 * u + c2 * v);
+            }
             let front_face = ray.direction().dot(self.normal) < 0.0;
             if !front_face {
                 // dbg!(ray.origin() + t * ray.direction());
@@ -180,7 +241,11 @@ impl Hittable for Triangle {
                 normal: self.normal,
                 ray_distance: t,
                 front_face,
-                material: self.material,
+                material,
+                bary: vec2(u, v),
+                uv: self
+                    .uvs
+                    .map(|[uv0, uv1, uv2]| uv0 * (1.0 - u - v) + uv1 * u + uv2 * v),
             })
         } else {
             None
@@ -188,6 +253,9 @@ impl Hittable for Triangle {
     }
 }
 
+// minimum bounding box thickness along any axis, as a fraction of the largest extent
+pub const MIN_BOX_THICKNESS: f32 = 1e-4;
+
 #[derive(Clone, Debug)]
 pub struct BoundingBox {
     range_x: Range<f32>,
@@ -203,12 +271,32 @@ impl BoundingBox {
             _ => self.range_x.clone(),
         }
     }
+    pub fn min(&self) -> Vec3 {
+        Vec3::new(self.range_x.start, self.range_y.start, self.range_z.start)
+    }
+    pub fn max(&self) -> Vec3 {
+        Vec3::new(self.range_x.end, self.range_y.end, self.range_z.end)
+    }
+    pub fn center(&self) -> Vec3 {
+        (self.min() + self.max()) / 2.0
+    }
+    // smallest box containing both
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let (min, max) = (self.min().min(other.min()), self.max().max(other.max()));
+        BoundingBox {
+            range_x: min.x..max.x,
+            range_y: min.y..max.y,
+            range_z: min.z..max.z,
+        }
+    }
 }
 
 impl Hittable for BoundingBox {
     fn hit_point(&self, ray: &crate::ray::Ray, _min_distance: f32) -> Option<HitInfo> {
         let mut min_t = f32::NEG_INFINITY;
         let mut max_t = f32::INFINITY;
+        // outward normal of the slab face the ray enters through
+        let mut normal = Vec3::ZERO;
 
         for i in 0..3 {
             let inv_dir = 1.0 / ray.direction()[i];
@@ -222,23 +310,190 @@ impl Hittable for BoundingBox {
             }
 
             // ignore minimum distances for bounding box intersections
-            min_t = min_t.max(t0);
+            if t0 > min_t {
+                min_t = t0;
+                normal = Vec3::ZERO;
+                normal[i] = -inv_dir.signum();
+            }
             max_t = max_t.min(t1);
 
             if max_t <= min_t {
                 return None;
             }
         }
+        // the whole box is behind the ray
+        if max_t < 0.0 {
+            return None;
+        }
         Some(HitInfo {
             position: ray.origin() + min_t * ray.direction(),
-            normal: Vec3::splat(0.0),
+            normal,
             ray_distance: min_t,
             front_face: true,
             material: Material::default(),
+            bary: Vec2::ZERO,
+            uv: None,
         })
     }
 }
 
+#[derive(Debug)]
+pub enum MeshLoadError {
+    Io(std::io::Error),
+    Gltf(gltf::Error),
+    // malformed PLY, or a layout the reader doesn't handle
+    Ply(String),
+    // malformed OBJ
+    Obj(String),
+    // the file has no mesh, or its first primitive has no positions
+    NoMesh,
+    // only triangle lists, strips and fans can be turned into triangles
+    UnsupportedPrimitive(gltf::mesh::Mode),
+}
+
+impl fmt::Display for MeshLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read mesh file: {e}"),
+            Self::Gltf(e) => write!(f, "failed to read glTF: {e}"),
+            Self::Ply(message) => write!(f, "failed to read PLY: {message}"),
+            Self::Obj(message) => write!(f, "failed to read OBJ: {message}"),
+            Self::NoMesh => write!(f, "file contains no mesh"),
+            Self::UnsupportedPrimitive(mode) => {
+                write!(f, "unsupported primitive mode {mode:?}, expected triangles")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshLoadError {}
+
+impl From<std::io::Error> for MeshLoadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<gltf::Error> for MeshLoadError {
+    fn from(e: gltf::Error) -> Self {
+        Self::Gltf(e)
+    }
+}
+
+// first node in a depth-first walk that has a mesh, with its world transform
+fn find_gltf_mesh(node: gltf::Node, parent: Mat4) -> Option<(gltf::Mesh, Mat4)> {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        return Some((mesh, transform));
+    }
+    node.children()
+        .find_map(|child| find_gltf_mesh(child, transform))
+}
+
+// triangles smaller than this, as a fraction of the squared largest mesh extent, count as
+// degenerate
+pub const MIN_TRIANGLE_AREA: f32 = 1e-10;
+
+// problems found by Mesh::validate
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshReport {
+    // zero-area triangles, which have no normal
+    pub degenerate: usize,
+    // triangles with the same corners as an earlier one, in any order
+    pub duplicate: usize,
+    // edges not shared by exactly two triangles
+    pub non_manifold_edges: usize,
+    // edges whose two triangles both run along them in the same direction, so one of the
+    // pair has its normal flipped
+    pub inconsistent_edges: usize,
+}
+
+impl MeshReport {
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// exact bit pattern of a vertex, with -0.0 folded into 0.0
+fn vertex_key(v: Vec3) -> [u32; 3] {
+    (v + Vec3::ZERO).to_array().map(f32::to_bits)
+}
+
+fn triangle_key(t: &Triangle) -> [[u32; 3]; 3] {
+    let mut key = t.points.map(vertex_key);
+    key.sort_unstable();
+    key
+}
+
+// a cluster of adjacent, nearly coplanar triangles, see Mesh::facets
+#[derive(Clone, Debug)]
+pub struct Facet {
+    // area-weighted average of the triangle normals, unit length
+    pub normal: Vec3,
+    pub area: f32,
+    // indices into Mesh::triangle_slice
+    pub triangles: Vec<usize>,
+}
+
+impl Facet {
+    // angle between the two facets' planes in degrees, e.g. a crown facet against the table
+    pub fn angle_from(&self, other: &Facet) -> f32 {
+        self.normal
+            .dot(other.normal)
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees()
+    }
+}
+
+// facets tilted this far or less from the girdle plane can be the table
+const TABLE_TOLERANCE_DEGREES: f32 = 5.0;
+// facets steeper than this are part of the girdle rather than the crown or pavilion
+const GIRDLE_MIN_DEGREES: f32 = 80.0;
+// how far apart triangle normals can be and still count as one polished facet
+const CUT_FACET_TOLERANCE_DEGREES: f32 = 0.5;
+// refinement passes when aligning a rotated copy of a mesh with itself
+const SYMMETRY_ALIGNMENT_PASSES: usize = 8;
+// furthest the alignment may turn a rotated copy. enough to absorb a cutter's indexing error,
+// too little to slide the copy round onto some other symmetry of the stone
+const SYMMETRY_MAX_TURN_DEGREES: f32 = 2.0;
+
+// angles of a cut stone, all in degrees from the girdle plane, see Mesh::measure_angles
+#[derive(Clone, Debug, Default)]
+pub struct CutReport {
+    // tilt of the table, ideally 0
+    pub table_angle: f32,
+    // facets above the girdle, largest first
+    pub crown_angles: Vec<f32>,
+    // facets below the girdle, largest first. a faceted culet shows up here near 0
+    pub pavilion_angles: Vec<f32>,
+    pub girdle_facets: usize,
+}
+
+impl CutReport {
+    pub fn mean_crown_angle(&self) -> Option<f32> {
+        mean(&self.crown_angles)
+    }
+
+    pub fn mean_pavilion_angle(&self) -> Option<f32> {
+        mean(&self.pavilion_angles)
+    }
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+}
+
+// how far a mesh is from matching itself under rotation, see Mesh::symmetry_report.
+// deviations are distances in mesh units from each rotated vertex to the nearest vertex,
+// once each rotated copy has been aligned as well as it can be
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SymmetryReport {
+    pub folds: usize,
+    pub max_deviation: f32,
+    pub mean_deviation: f32,
+}
+
 #[derive(Clone, Debug)]
 pub struct Mesh {
     origin: Vec3,
@@ -246,12 +501,67 @@ pub struct Mesh {
     bounding_box: BoundingBox,
 }
 
+impl Mesh {
+    // whether any triangle is hit between `min_distance` and `max_distance`, stopping at the
+    // first one found instead of looking for the closest
+    pub(crate) fn hit_any(
+        &self,
+        ray: &crate::ray::Ray,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        !ray.is_degenerate()
+            && self.bounding_box.hit_by(ray, min_distance)
+            && self.triangles.iter().any(|t| {
+                t.hit_point(ray, min_distance)
+                    .is_some_and(|info| (min_distance..=max_distance).contains(&info.ray_distance))
+            })
+    }
+
+    // closest hits for the rays of `rays` selected by the `active` bitmask, written to
+    // `closest` where they are nearer than what it already holds. each triangle is tested
+    // against the whole packet in turn, the results match calling `hit_point` per ray
+    pub(crate) fn hit_packet(
+        &self,
+        rays: &[crate::ray::Ray],
+        mut active: u64,
+        min_distance: f32,
+        max_distance: f32,
+        closest: &mut [Option<HitInfo>],
+    ) {
+        for (i, ray) in rays.iter().enumerate() {
+            if ray.is_degenerate() || !self.bounding_box.hit_by(ray, min_distance) {
+                active &= !(1 << i);
+            }
+        }
+        for triangle in self.triangles.iter() {
+            let mut remaining = active;
+            while remaining != 0 {
+                let i = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                if let Some(info) = triangle.hit_point(&rays[i], min_distance) {
+                    let closer = match closest[i] {
+                        Some(c) => info.ray_distance < c.ray_distance,
+                        None => info.ray_distance <= max_distance,
+                    };
+                    if closer {
+                        closest[i] = Some(info);
+                    }
+                }
+            }
+        }
+    }
+}
+
 impl Hittable for Mesh {
     fn hit_point(
         &self,
         ray: &crate::ray::Ray,
         min_distance: f32,
     ) -> Option<crate::hittable::HitInfo> {
+        if ray.is_degenerate() {
+            return None;
+        }
         if self.bounding_box.hit_by(ray, min_distance) {
             self.triangles
                 .iter()
@@ -281,6 +591,107 @@ impl Mesh {
 
         Self::from_tris_with_material(origin, tris, Material::gem())
     }
+    // reads the first primitive of the first mesh in the default scene, with node transforms
+    // baked into the vertices. normals are recomputed per face, gems are flat shaded anyway
+    pub fn load_from_gltf<P: AsRef<Path>>(origin: Vec3, path: P) -> Result<Self, MeshLoadError> {
+        let (document, buffers, _images) = gltf::import(path)?;
+        let (mesh, transform) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .and_then(|scene| {
+                scene
+                    .nodes()
+                    .find_map(|node| find_gltf_mesh(node, Mat4::IDENTITY))
+            })
+            .ok_or(MeshLoadError::NoMesh)?;
+        let primitive = mesh.primitives().next().ok_or(MeshLoadError::NoMesh)?;
+
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .ok_or(MeshLoadError::NoMesh)?
+            .map(|p| transform.transform_point3(Vec3::from(p)))
+            .collect();
+        // glTF puts v = 0 at the top of the image, textures are sampled with it at the bottom
+        let uvs: Option<Vec<Vec2>> = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| Vec2::new(u, 1.0 - v)).collect());
+        let indices: Vec<u32> = reader
+            .read_indices()
+            .map(|indices| indices.into_u32().collect())
+            .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+        let corners: Vec<[u32; 3]> = match primitive.mode() {
+            gltf::mesh::Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|i| [i[0], i[1], i[2]])
+                .collect(),
+            // every other strip triangle is wound backwards
+            gltf::mesh::Mode::TriangleStrip => (2..indices.len())
+                .map(|i| match i % 2 {
+                    0 => [indices[i - 2], indices[i - 1], indices[i]],
+                    _ => [indices[i - 1], indices[i - 2], indices[i]],
+                })
+                .collect(),
+            gltf::mesh::Mode::TriangleFan => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            mode => return Err(MeshLoadError::UnsupportedPrimitive(mode)),
+        };
+
+        // mirroring transforms flip the winding, and with it the face normals
+        let mirrored = transform.determinant() < 0.0;
+        let tris = corners.into_iter().map(|[i0, i1, i2]| {
+            let [i0, i1, i2] =
+                if mirrored { [i0, i2, i1] } else { [i0, i1, i2] }.map(|i| i as usize);
+            let tri = Triangle::new(positions[i0], positions[i1], positions[i2]);
+            match &uvs {
+                Some(uvs) => tri.with_uvs([uvs[i0], uvs[i1], uvs[i2]]),
+                None => tri,
+            }
+        });
+
+        Ok(Self::from_tris_with_material(origin, tris, Material::gem()))
+    }
+    // ascii or binary PLY, with polygons fan triangulated. vertex colors are kept on the
+    // triangles and show once the mesh is given a Diffuse material
+    pub fn load_from_ply<P: AsRef<Path>>(origin: Vec3, path: P) -> Result<Self, MeshLoadError> {
+        let ply = ply::read_ply(BufReader::new(File::open(path)?))?;
+        let tris = ply
+            .faces
+            .iter()
+            .flat_map(|face| (2..face.len()).map(move |i| [face[0], face[i - 1], face[i]]))
+            .map(|[i0, i1, i2]| {
+                let tri = Triangle::new(ply.positions[i0], ply.positions[i1], ply.positions[i2]);
+                match &ply.colors {
+                    Some(colors) => tri.with_vertex_colors([colors[i0], colors[i1], colors[i2]]),
+                    None => tri,
+                }
+            });
+
+        Ok(Self::from_tris_with_material(origin, tris, Material::gem()))
+    }
+    // OBJ geometry with polygons fan triangulated. triangles whose corners all have texture
+    // coordinates keep them, for Material::Textured
+    pub fn load_from_obj<P: AsRef<Path>>(origin: Vec3, path: P) -> Result<Self, MeshLoadError> {
+        let obj = obj::read_obj(BufReader::new(File::open(path)?))?;
+        let tris = obj
+            .faces
+            .iter()
+            .flat_map(|face| (2..face.len()).map(move |i| [face[0], face[i - 1], face[i]]))
+            .map(|corners| {
+                let [p0, p1, p2] = corners.map(|(p, _)| obj.positions[p]);
+                let tri = Triangle::new(p0, p1, p2);
+                match corners.map(|(_, uv)| uv) {
+                    [Some(uv0), Some(uv1), Some(uv2)] => {
+                        tri.with_uvs([obj.uvs[uv0], obj.uvs[uv1], obj.uvs[uv2]])
+                    }
+                    _ => tri,
+                }
+            });
+
+        Ok(Self::from_tris_with_material(origin, tris, Material::gem()))
+    }
     pub fn from_tris_with_material<I, T>(origin: Vec3, tris: I, material: Material) -> Self
     where
         I: IntoIterator<Item = T>,
@@ -324,49 +735,44 @@ impl Mesh {
             }
         }
 
-        // don't allow BBs with zero dimensions
+        // don't allow BBs with zero dimensions, padding flat axes relative to the mesh's size
+        let max_extent = (max_x - min_x).max(max_y - min_y).max(max_z - min_z);
+        let min_thickness = (MIN_BOX_THICKNESS * max_extent).max(f32::EPSILON);
         Self {
             origin,
             triangles: tris,
             bounding_box: BoundingBox {
-                range_x: min_x..max_x.max(min_x + 0.1),
-                range_y: min_y..max_y.max(min_y + 0.1),
-                range_z: min_z..max_z.max(min_z + 0.1),
+                range_x: min_x..max_x.max(min_x + min_thickness),
+                range_y: min_y..max_y.max(min_y + min_thickness),
+                range_z: min_z..max_z.max(min_z + min_thickness),
             },
         }
     }
 
+    // triangles are already in world space, so the merged mesh sits at the origin
+    pub fn merge<I>(meshes: I) -> Self
+    where
+        I: IntoIterator<Item = Mesh>,
+    {
+        Self::from_tris(Vec3::ZERO, meshes.into_iter().flat_map(|m| m.triangles))
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.triangles
+            .iter_mut()
+            .for_each(|t| t.material = material);
+    }
+
+    pub fn set_double_sided(&mut self, double_sided: bool) {
+        self.triangles
+            .iter_mut()
+            .for_each(|t| t.double_sided = double_sided);
+    }
+
     pub fn apply_color(&mut self, new_color: Vec3) -> bool {
         let mut changed = false;
         for t in self.triangles.iter_mut() {
-            match t.material {
-                Material::Refractive {
-                    color,
-                    refractive_index,
-                    dispersion,
-                } => {
-                    if new_color != color {
-                        t.material = Material::Refractive {
-                            color: new_color,
-                            refractive_index,
-                            dispersion,
-                        };
-                        changed = true;
-                    }
-                }
-                Material::Diffuse { color } => {
-                    if new_color != color {
-                        t.material = Material::Diffuse { color: new_color };
-                        changed = true;
-                    }
-                }
-                Material::Light { color } => {
-                    if new_color != color {
-                        t.material = Material::Light { color: new_color };
-                        changed = true
-                    }
-                }
-            }
+            changed |= t.material.set_color(new_color);
         }
         changed
     }
@@ -374,43 +780,303 @@ impl Mesh {
     pub fn apply_ri(&mut self, new_ri: f32) -> bool {
         let mut changed = false;
         for t in self.triangles.iter_mut() {
-            if let Material::Refractive {
-                color,
-                refractive_index: _,
-                dispersion,
-            } = t.material
-            {
-                t.material = Material::Refractive {
-                    color,
-                    refractive_index: new_ri,
-                    dispersion,
-                };
-                changed = true;
-            }
+            changed |= t.material.set_refractive_index(new_ri);
         }
         changed
     }
     pub fn apply_dispersion(&mut self, new_dispersion: f32) -> bool {
         let mut changed = false;
         for t in self.triangles.iter_mut() {
-            if let Material::Refractive {
-                color,
-                refractive_index,
-                dispersion: _,
-            } = t.material
-            {
-                t.material = Material::Refractive {
-                    color,
-                    refractive_index,
-                    dispersion: new_dispersion,
-                };
+            if let Material::Refractive { dispersion, .. } = &mut t.material {
+                if *dispersion == new_dispersion {
+                    continue;
+                }
+                *dispersion = new_dispersion;
                 changed = true;
             }
         }
         changed
     }
 
+    fn is_degenerate(&self, t: &Triangle) -> bool {
+        let extent = self.bounding_box.max() - self.bounding_box.min();
+        t.normal == Vec3::ZERO || t.area() < MIN_TRIANGLE_AREA * extent.max_element().powi(2)
+    }
+
+    pub fn validate(&self) -> MeshReport {
+        let mut report = MeshReport::default();
+        let mut seen = HashSet::new();
+        // uses of each edge in either direction
+        let mut edges: HashMap<_, [usize; 2]> = HashMap::new();
+        for t in self.triangles.iter() {
+            if self.is_degenerate(t) {
+                report.degenerate += 1;
+            }
+            if !seen.insert(triangle_key(t)) {
+                report.duplicate += 1;
+            }
+            for i in 0..3 {
+                let (a, b) = (vertex_key(t[i]), vertex_key(t[(i + 1) % 3]));
+                edges.entry((a.min(b), a.max(b))).or_default()[(a > b) as usize] += 1;
+            }
+        }
+        for [forward, backward] in edges.into_values() {
+            if forward + backward != 2 {
+                report.non_manifold_edges += 1;
+            } else if forward != 1 {
+                report.inconsistent_edges += 1;
+            }
+        }
+        report
+    }
+
+    // group triangles into facets, flooding across shared edges while a neighbour's normal is
+    // within `angle_tolerance` degrees of the triangle the facet started from. comparing to the
+    // first triangle rather than the neighbour stops a gently curved girdle from chaining into
+    // one facet. largest first, so on most cuts the table comes out as facets[0].
+    // degenerate triangles belong to no facet
+    pub fn facets(&self, angle_tolerance: f32) -> Vec<Facet> {
+        let cos_tolerance = angle_tolerance.to_radians().cos();
+        let mut edges: HashMap<_, Vec<usize>> = HashMap::new();
+        for (i, t) in self.triangles.iter().enumerate() {
+            for j in 0..3 {
+                let (a, b) = (vertex_key(t[j]), vertex_key(t[(j + 1) % 3]));
+                edges.entry((a.min(b), a.max(b))).or_default().push(i);
+            }
+        }
+
+        let mut assigned: Vec<bool> = self
+            .triangles
+            .iter()
+            .map(|t| self.is_degenerate(t))
+            .collect();
+        let mut facets = Vec::new();
+        for seed in 0..self.triangles.len() {
+            if assigned[seed] {
+                continue;
+            }
+            assigned[seed] = true;
+            let seed_normal = self.triangles[seed].normal;
+            let mut triangles = vec![seed];
+            let mut stack = vec![seed];
+            while let Some(i) = stack.pop() {
+                let t = &self.triangles[i];
+                for j in 0..3 {
+                    let (a, b) = (vertex_key(t[j]), vertex_key(t[(j + 1) % 3]));
+                    for &n in &edges[&(a.min(b), a.max(b))] {
+                        if !assigned[n]
+                            && self.triangles[n].normal.dot(seed_normal) >= cos_tolerance
+                        {
+                            assigned[n] = true;
+                            triangles.push(n);
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+
+            let (weighted, area) = triangles.iter().fold((Vec3::ZERO, 0.0), |(n, a), &i| {
+                let t = &self.triangles[i];
+                (n + t.area() * t.normal, a + t.area())
+            });
+            facets.push(Facet {
+                normal: weighted.normalize_or_zero(),
+                area,
+                triangles,
+            });
+        }
+        facets.sort_by(|a, b| b.area.total_cmp(&a.area));
+        facets
+    }
+
+    // crown and pavilion angles as a grading lab reports them. `table_normal` points out of the
+    // table and the girdle plane is taken perpendicular to it. None if no facet faces along
+    // `table_normal` closely enough to be the table
+    pub fn measure_angles(&self, table_normal: Vec3) -> Option<CutReport> {
+        let up = table_normal.normalize();
+        let facets = self.facets(CUT_FACET_TOLERANCE_DEGREES);
+        let tilt = |facet: &Facet| facet.normal.dot(up).clamp(-1.0, 1.0).acos().to_degrees();
+
+        let table = facets
+            .iter()
+            .position(|facet| tilt(facet) <= TABLE_TOLERANCE_DEGREES)?;
+        let mut report = CutReport {
+            table_angle: tilt(&facets[table]),
+            ..Default::default()
+        };
+        for (i, facet) in facets.iter().enumerate() {
+            let angle = tilt(facet);
+            if i == table {
+                continue;
+            } else if angle < GIRDLE_MIN_DEGREES {
+                report.crown_angles.push(angle);
+            } else if angle > 180.0 - GIRDLE_MIN_DEGREES {
+                report.pavilion_angles.push(180.0 - angle);
+            } else {
+                report.girdle_facets += 1;
+            }
+        }
+        Some(report)
+    }
+
+    // rotate every vertex by each multiple of 1/`folds` of a turn about `axis` and measure how
+    // far it lands from the nearest vertex, 8 folds for a round brilliant. `axis` only gives
+    // the direction: each rotated copy starts out turned about the centroid, then the turn
+    // angle (by up to SYMMETRY_MAX_TURN_DEGREES) and the axis position are refined to the
+    // alignment that fits the mesh best, so a stone isn't marked down for where its axis
+    // happens to sit or for facets that are all indexed a little off. nearest vertices are
+    // found by brute force on every pass, fine for cut stones but slow for scanned meshes
+    // with hundreds of thousands of vertices
+    pub fn symmetry_report(&self, axis: Vec3, folds: usize) -> SymmetryReport {
+        let mut seen = HashSet::new();
+        let vertices: Vec<Vec3> = self
+            .triangles
+            .iter()
+            .flat_map(|t| t.points)
+            .filter(|&v| seen.insert(vertex_key(v)))
+            .collect();
+        if vertices.is_empty() || folds < 2 {
+            return SymmetryReport {
+                folds,
+                ..Default::default()
+            };
+        }
+
+        let center = self.centroid();
+        let axis = axis.normalize();
+        let (mut max_deviation, mut total) = (0.0f32, 0.0);
+        for k in 1..folds {
+            let rotation =
+                Quat::from_axis_angle(axis, std::f32::consts::TAU * k as f32 / folds as f32);
+            let deviations = align_rotated(&vertices, axis, rotation, center - rotation * center);
+            max_deviation = deviations.iter().fold(max_deviation, |m, &d| m.max(d));
+            total += deviations.iter().sum::<f32>();
+        }
+        SymmetryReport {
+            folds,
+            max_deviation,
+            mean_deviation: total / ((folds - 1) * vertices.len()) as f32,
+        }
+    }
+
+    // area-weighted center of the surface
+    pub fn centroid(&self) -> Vec3 {
+        let (weighted, area) =
+            self.triangles
+                .iter()
+                .fold((Vec3::ZERO, 0.0), |(weighted, area), t| {
+                    let a = t.area();
+                    (weighted + a * (t[0] + t[1] + t[2]) / 3.0, area + a)
+                });
+        weighted / area
+    }
+
+    // volume enclosed by a closed mesh, negative when its normals point inwards
+    pub fn signed_volume(&self) -> f32 {
+        self.triangles
+            .iter()
+            .map(|t| t[0].dot(t[1].cross(t[2])) / 6.0)
+            .sum()
+    }
+
+    // drop degenerate and duplicate triangles, returning how many were removed.
+    // non-manifold edges are only reported, there's no safe automatic fix for them
+    pub fn repair(&mut self) -> usize {
+        let before = self.triangles.len();
+        let mut seen = HashSet::new();
+        let triangles: Vec<_> = self
+            .triangles
+            .iter()
+            .filter(|t| !self.is_degenerate(t) && seen.insert(triangle_key(t)))
+            .copied()
+            .collect();
+        // triangles are already in world space, only the bounding box needs refitting
+        *self = Self {
+            origin: self.origin,
+            ..Self::from_tris(Vec3::ZERO, triangles)
+        };
+        before - self.triangles.len()
+    }
+
+    // for chaining onto a loader, e.g. Mesh::load_from_stl(origin, path).repaired()
+    pub fn repaired(mut self) -> Self {
+        self.repair();
+        self
+    }
+
     pub fn triangle_slice(&self) -> &[Triangle] {
         &self.triangles
     }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn bounding_box(&self) -> &BoundingBox {
+        &self.bounding_box
+    }
+
+    pub fn origin(&self) -> Vec3 {
+        self.origin
+    }
+}
+
+// distance from each of `vertices`, moved by `rotation` then `translation`, to the nearest of
+// `vertices`, after refining the move by a turn about `axis` and a shift across it. each pass
+// pairs every moved vertex with its nearest neighbour and fits the 2D rigid motion in the
+// plane across the axis that best lines the pairs up, keeping the best alignment seen
+fn align_rotated(vertices: &[Vec3], axis: Vec3, rotation: Quat, translation: Vec3) -> Vec<f32> {
+    let (e1, e2) = axis.any_orthonormal_pair();
+    let normal = e1.cross(e2);
+    let across = |v: Vec3| vec2(v.dot(e1), v.dot(e2));
+    let max_turn = SYMMETRY_MAX_TURN_DEGREES.to_radians();
+    let (mut rotation, mut translation, mut turned) = (rotation, translation, 0.0f32);
+    let mut best: Option<Vec<f32>> = None;
+    for _ in 0..SYMMETRY_ALIGNMENT_PASSES {
+        let moved: Vec<Vec3> = vertices
+            .iter()
+            .map(|&v| rotation * v + translation)
+            .collect();
+        let nearest: Vec<Vec3> = moved
+            .iter()
+            .map(|&m| {
+                vertices
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| a.distance_squared(m).total_cmp(&b.distance_squared(m)))
+                    .unwrap()
+            })
+            .collect();
+        let deviations: Vec<f32> = moved
+            .iter()
+            .zip(nearest.iter())
+            .map(|(m, n)| m.distance(*n))
+            .collect();
+        let total: f32 = deviations.iter().sum();
+        if best
+            .as_ref()
+            .is_some_and(|best| best.iter().sum::<f32>() <= total)
+        {
+            break;
+        }
+        best = Some(deviations);
+
+        // least squares rotation and shift taking the moved vertices onto their neighbours
+        let count = vertices.len() as f32;
+        let moved_mean = moved.iter().map(|&m| across(m)).sum::<Vec2>() / count;
+        let nearest_mean = nearest.iter().map(|&n| across(n)).sum::<Vec2>() / count;
+        let (mut sin, mut cos) = (0.0f32, 0.0f32);
+        for (&m, &n) in moved.iter().zip(nearest.iter()) {
+            let (a, b) = (across(m) - moved_mean, across(n) - nearest_mean);
+            sin += a.perp_dot(b);
+            cos += a.dot(b);
+        }
+        let angle = (turned + sin.atan2(cos)).clamp(-max_turn, max_turn) - turned;
+        turned += angle;
+        let turn = Quat::from_axis_angle(normal, angle);
+        let shift = nearest_mean - Vec2::from_angle(angle).rotate(moved_mean);
+        rotation = turn * rotation;
+        translation = turn * translation + shift.x * e1 + shift.y * e2;
+    }
+    best.unwrap_or_default()
 }
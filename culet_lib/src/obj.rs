@@ -0,0 +1,106 @@
+// reader for the geometry of Wavefront OBJ files: vertex positions, texture coordinates and
+// polygon faces. normals, groups and materials are skipped
+use std::io::BufRead;
+
+use glam::{Vec2, Vec3};
+
+use crate::mesh::MeshLoadError;
+
+pub(crate) struct ObjMesh {
+    pub positions: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    // (position, uv) indices of each polygon's corners, both checked against their lists
+    pub faces: Vec<Vec<(usize, Option<usize>)>>,
+}
+
+fn obj_error(line: usize, message: impl Into<String>) -> MeshLoadError {
+    MeshLoadError::Obj(format!("line {line}: {}", message.into()))
+}
+
+pub(crate) fn read_obj(reader: impl BufRead) -> Result<ObjMesh, MeshLoadError> {
+    let mut mesh = ObjMesh {
+        positions: Vec::new(),
+        uvs: Vec::new(),
+        faces: Vec::new(),
+    };
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let number = i + 1;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                let [x, y, z] = numbers(number, &mut words)?;
+                mesh.positions.push(Vec3::new(x, y, z));
+            }
+            Some("vt") => {
+                // a third `w` coordinate is allowed and ignored
+                let [u, v] = numbers(number, &mut words)?;
+                mesh.uvs.push(Vec2::new(u, v));
+            }
+            Some("f") => {
+                let face = words
+                    .map(|corner| face_corner(number, corner, &mesh))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if face.len() < 3 {
+                    return Err(obj_error(number, "face has fewer than 3 corners"));
+                }
+                mesh.faces.push(face);
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+// the first N numbers on the line
+fn numbers<'a, const N: usize>(
+    line: usize,
+    words: &mut impl Iterator<Item = &'a str>,
+) -> Result<[f32; N], MeshLoadError> {
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        let word = words
+            .next()
+            .ok_or_else(|| obj_error(line, format!("expected {N} numbers")))?;
+        *value = word
+            .parse()
+            .map_err(|_| obj_error(line, format!("invalid number `{word}`")))?;
+    }
+    Ok(values)
+}
+
+// `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn face_corner(
+    line: usize,
+    corner: &str,
+    mesh: &ObjMesh,
+) -> Result<(usize, Option<usize>), MeshLoadError> {
+    let mut indices = corner.split('/');
+    let position = indices.next().unwrap_or_default();
+    let position = vertex_index(line, position, mesh.positions.len())?;
+    let uv = match indices.next() {
+        None | Some("") => None,
+        Some(uv) => Some(vertex_index(line, uv, mesh.uvs.len())?),
+    };
+    Ok((position, uv))
+}
+
+// OBJ indices start at 1, and negative ones count back from the most recent entry
+fn vertex_index(line: usize, word: &str, count: usize) -> Result<usize, MeshLoadError> {
+    let index: i64 = word
+        .parse()
+        .map_err(|_| obj_error(line, format!("invalid index `{word}`")))?;
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => count as i64 + index,
+        0 => -1,
+    };
+    if (0..count as i64).contains(&resolved) {
+        Ok(resolved as usize)
+    } else {
+        Err(obj_error(
+            line,
+            format!("index {index} is out of range for {count} entries"),
+        ))
+    }
+}
@@ -0,0 +1,201 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+use rand::Rng;
+
+use crate::{
+    hittable::Hittable,
+    material::Material,
+    ray::Ray,
+    render::{cosine_sample_hemisphere, fresnel, reflect, refract, RenderOptions},
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Photon {
+    pub position: Vec3,
+    pub power: Vec3,
+}
+
+// caustic photons (light that reached a diffuse surface through refractive media),
+// stored as an implicit kd-tree: each range is split at its median photon
+#[derive(Clone, Debug)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    split_axes: Vec<u8>,
+    gather_radius: f32,
+}
+
+impl PhotonMap {
+    pub fn emit(
+        options: &RenderOptions,
+        photon_count: usize,
+        gather_radius: f32,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let scene = &options.scene;
+        let mut photons = vec![];
+
+        for _ in 0..photon_count {
+            let Some(light) = scene.sample_light(rng) else {
+                break;
+            };
+            // lights emit from both faces, total flux is 2π * radiance * area
+            let side = if rng.gen::<bool>() { 1.0 } else { -1.0 };
+            let direction = cosine_sample_hemisphere(light.normal * side, rng);
+            let mut power = light.emission * 2.0 * PI / (light.pdf_area * photon_count as f32);
+            let mut ray = Ray::new(light.position, direction);
+            let mut through_refractive = false;
+
+            for _ in 0..options.photon_max_depth {
+                let Some(hit) = scene.hit_point(&ray, scene.shadow_bias()) else {
+                    break;
+                };
+                match hit.material {
+                    Material::Refractive {
+                        color: absorption,
+                        refractive_index,
+                        ..
+                    }
+                    | Material::ThinFilm {
+                        color: absorption,
+                        refractive_index,
+                        ..
+                    } => {
+                        through_refractive = true;
+                        let (normal, eta_i, eta_t) = if hit.front_face {
+                            (hit.normal, options.exterior_ri, refractive_index)
+                        } else {
+                            // Beer's law along the segment inside the medium
+                            power *= (-absorption * hit.ray_distance).exp();
+                            (-hit.normal, refractive_index, options.exterior_ri)
+                        };
+                        // pick reflection or refraction in proportion to the Fresnel term
+                        let direction =
+                            if rng.gen::<f32>() < fresnel(ray.direction(), normal, eta_i, eta_t) {
+                                reflect(ray.direction(), normal)
+                            } else {
+                                refract(ray.direction(), normal, eta_i / eta_t)
+                            };
+                        ray = Ray::new(hit.position, direction);
+                    }
+                    Material::Diffuse { .. }
+                    | Material::Anisotropic { .. }
+                    | Material::Checker { .. }
+                    | Material::Textured { .. } => {
+                        if through_refractive {
+                            photons.push(Photon {
+                                position: hit.position,
+                                power,
+                            });
+                        }
+                        break;
+                    }
+                    Material::Light { .. } => break,
+                }
+            }
+        }
+
+        let mut split_axes = vec![0; photons.len()];
+        build_kd_tree(&mut photons, &mut split_axes);
+        Self {
+            photons,
+            split_axes,
+            gather_radius,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    // density estimate of caustic light reflected by a Lambertian surface at `position`
+    pub fn radiance(&self, position: Vec3, albedo: Vec3) -> Vec3 {
+        let mut power = Vec3::ZERO;
+        gather(
+            &self.photons,
+            &self.split_axes,
+            position,
+            self.gather_radius * self.gather_radius,
+            &mut |photon| power += photon.power,
+        );
+        let area = PI * self.gather_radius * self.gather_radius;
+        albedo / PI * power / area
+    }
+}
+
+fn build_kd_tree(photons: &mut [Photon], split_axes: &mut [u8]) {
+    if photons.len() <= 1 {
+        return;
+    }
+
+    // split along the widest axis of this range
+    let (min, max) = photons.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), p| (min.min(p.position), max.max(p.position)),
+    );
+    let extent = max - min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+
+    let median = photons.len() / 2;
+    photons.select_nth_unstable_by(median, |a, b| a.position[axis].total_cmp(&b.position[axis]));
+    split_axes[median] = axis as u8;
+
+    let (left, right) = photons.split_at_mut(median);
+    let (left_axes, right_axes) = split_axes.split_at_mut(median);
+    build_kd_tree(left, left_axes);
+    build_kd_tree(&mut right[1..], &mut right_axes[1..]);
+}
+
+fn gather(
+    photons: &[Photon],
+    split_axes: &[u8],
+    position: Vec3,
+    radius_squared: f32,
+    visit: &mut impl FnMut(&Photon),
+) {
+    if photons.is_empty() {
+        return;
+    }
+    let median = photons.len() / 2;
+    let photon = &photons[median];
+    if photon.position.distance_squared(position) <= radius_squared {
+        visit(photon);
+    }
+
+    let axis = split_axes[median] as usize;
+    let offset = position[axis] - photon.position[axis];
+    let (left, right) = (0..median, median + 1..photons.len());
+    let (near, far) = if offset < 0.0 {
+        (left, right)
+    } else {
+        (right, left)
+    };
+
+    gather(
+        &photons[near.clone()],
+        &split_axes[near],
+        position,
+        radius_squared,
+        visit,
+    );
+    // the far side can only hold photons in range if the splitting plane is
+    if offset * offset <= radius_squared {
+        gather(
+            &photons[far.clone()],
+            &split_axes[far],
+            position,
+            radius_squared,
+            visit,
+        );
+    }
+}
@@ -0,0 +1,352 @@
+// reader for the subset of PLY that meshes need: ascii and binary vertex and face elements,
+// anything else in the file is parsed and skipped
+use std::io::BufRead;
+
+use glam::Vec3;
+
+use crate::mesh::MeshLoadError;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, MeshLoadError> {
+        Ok(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return Err(ply_error(format!("unknown property type `{name}`"))),
+        })
+    }
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+    // integer colors span the type's range, float colors are already in [0, 1]
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::U16 => 65535.0,
+            Self::F32 | Self::F64 => 1.0,
+            _ => 255.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar {
+        name: String,
+        ty: ScalarType,
+    },
+    List {
+        name: String,
+        count_ty: ScalarType,
+        item_ty: ScalarType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Self::Scalar { name, .. } | Self::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn scalar(&self, name: &str) -> Option<(usize, ScalarType)> {
+        self.properties
+            .iter()
+            .enumerate()
+            .find_map(|(i, property)| match property {
+                Property::Scalar { name: n, ty } if n == name => Some((i, *ty)),
+                _ => None,
+            })
+    }
+}
+
+enum Value {
+    Scalar(f64),
+    List(Vec<f64>),
+}
+
+impl Value {
+    fn scalar(&self) -> f64 {
+        match self {
+            Self::Scalar(value) => *value,
+            Self::List(_) => 0.0,
+        }
+    }
+}
+
+// the body of the file after the header
+struct Data<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Data<'a> {
+    fn new(format: Format, bytes: &'a [u8]) -> Result<Self, MeshLoadError> {
+        let text = match format {
+            Format::Ascii => {
+                std::str::from_utf8(bytes).map_err(|_| ply_error("ascii data is not valid text"))?
+            }
+            _ => "",
+        };
+        Ok(Self {
+            format,
+            bytes,
+            tokens: text.split_ascii_whitespace(),
+        })
+    }
+
+    fn scalar(&mut self, ty: ScalarType) -> Result<f64, MeshLoadError> {
+        if self.format == Format::Ascii {
+            let token = self
+                .tokens
+                .next()
+                .ok_or_else(|| ply_error("unexpected end of data"))?;
+            return token
+                .parse()
+                .map_err(|_| ply_error(format!("invalid number `{token}`")));
+        }
+
+        let size = ty.size();
+        if self.bytes.len() < size {
+            return Err(ply_error("unexpected end of data"));
+        }
+        let (raw, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        // normalise to little endian
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(raw);
+        if self.format == Format::BinaryBigEndian {
+            b[..size].reverse();
+        }
+        Ok(match ty {
+            ScalarType::I8 => b[0] as i8 as f64,
+            ScalarType::U8 => b[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(b),
+        })
+    }
+
+    fn record(&mut self, element: &Element) -> Result<Vec<Value>, MeshLoadError> {
+        element
+            .properties
+            .iter()
+            .map(|property| match *property {
+                Property::Scalar { ty, .. } => self.scalar(ty).map(Value::Scalar),
+                Property::List {
+                    count_ty, item_ty, ..
+                } => {
+                    let count = self.scalar(count_ty)? as usize;
+                    (0..count)
+                        .map(|_| self.scalar(item_ty))
+                        .collect::<Result<_, _>>()
+                        .map(Value::List)
+                }
+            })
+            .collect()
+    }
+}
+
+pub(crate) struct PlyMesh {
+    pub positions: Vec<Vec3>,
+    // in [0, 1], only when the vertices have red, green and blue properties
+    pub colors: Option<Vec<Vec3>>,
+    // vertex indices of each polygon, checked against `positions`
+    pub faces: Vec<Vec<usize>>,
+}
+
+fn ply_error(message: impl Into<String>) -> MeshLoadError {
+    MeshLoadError::Ply(message.into())
+}
+
+pub(crate) fn read_ply(mut reader: impl BufRead) -> Result<PlyMesh, MeshLoadError> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != "ply" {
+        return Err(ply_error("not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(ply_error("header has no end_header line"));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| ply_error(format!("invalid {name} count `{count}`")))?,
+                properties: Vec::new(),
+            }),
+            ["property", rest @ ..] => {
+                let property = match rest {
+                    ["list", count_ty, item_ty, name] => Property::List {
+                        name: name.to_string(),
+                        count_ty: ScalarType::parse(count_ty)?,
+                        item_ty: ScalarType::parse(item_ty)?,
+                    },
+                    [ty, name] => Property::Scalar {
+                        name: name.to_string(),
+                        ty: ScalarType::parse(ty)?,
+                    },
+                    _ => return Err(ply_error(format!("malformed `{}`", line.trim()))),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| ply_error("property declared before any element"))?
+                    .properties
+                    .push(property);
+            }
+            ["end_header"] => break,
+            _ => {
+                return Err(ply_error(format!(
+                    "unsupported header line `{}`",
+                    line.trim()
+                )))
+            }
+        }
+    }
+    let format = format.ok_or_else(|| ply_error("header has no format line"))?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut data = Data::new(format, &bytes)?;
+
+    let mut mesh = PlyMesh {
+        positions: Vec::new(),
+        colors: None,
+        faces: Vec::new(),
+    };
+    for element in elements.iter() {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut data, &mut mesh)?,
+            "face" => read_faces(element, &mut data, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    data.record(element)?;
+                }
+            }
+        }
+    }
+
+    let vertex_count = mesh.positions.len();
+    if let Some(&index) = mesh.faces.iter().flatten().find(|&&i| i >= vertex_count) {
+        return Err(ply_error(format!(
+            "face refers to vertex {index} of {vertex_count}"
+        )));
+    }
+    Ok(mesh)
+}
+
+fn read_vertices(
+    element: &Element,
+    data: &mut Data,
+    mesh: &mut PlyMesh,
+) -> Result<(), MeshLoadError> {
+    let [x, y, z] = ["x", "y", "z"].map(|name| element.scalar(name));
+    let (Some((x, _)), Some((y, _)), Some((z, _))) = (x, y, z) else {
+        return Err(ply_error("vertex element needs x, y and z properties"));
+    };
+    let rgb = match ["red", "green", "blue"].map(|name| element.scalar(name)) {
+        [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+        _ => None,
+    };
+
+    let mut colors = Vec::new();
+    for _ in 0..element.count {
+        let record = data.record(element)?;
+        let value = |i: usize| record[i].scalar() as f32;
+        mesh.positions.push(Vec3::new(value(x), value(y), value(z)));
+        if let Some(rgb) = rgb {
+            colors.push(Vec3::from_array(
+                rgb.map(|(i, ty)| (record[i].scalar() / ty.color_scale()) as f32),
+            ));
+        }
+    }
+    if rgb.is_some() {
+        mesh.colors = Some(colors);
+    }
+    Ok(())
+}
+
+fn read_faces(element: &Element, data: &mut Data, mesh: &mut PlyMesh) -> Result<(), MeshLoadError> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|property| {
+            matches!(property, Property::List { .. })
+                && matches!(property.name(), "vertex_indices" | "vertex_index")
+        })
+        .ok_or_else(|| ply_error("face element needs a vertex_indices list"))?;
+
+    for _ in 0..element.count {
+        let record = data.record(element)?;
+        if let Value::List(face) = &record[indices] {
+            let face = face
+                .iter()
+                .map(|&i| vertex_index(i))
+                .collect::<Result<_, _>>()?;
+            mesh.faces.push(face);
+        }
+    }
+    Ok(())
+}
+
+// indices can be stored as any type, so a float list could hold a NaN or a fraction and a
+// signed one a negative. `as usize` would quietly turn those into some other vertex
+fn vertex_index(value: f64) -> Result<usize, MeshLoadError> {
+    if value >= 0.0 && value.fract() == 0.0 && value <= u32::MAX as f64 {
+        Ok(value as usize)
+    } else {
+        Err(ply_error(format!("invalid vertex index `{value}`")))
+    }
+}
@@ -0,0 +1,145 @@
+use glam::Vec3;
+
+// image-space effects applied to a finished HDR buffer (row-major, `width * height` pixels)
+// before tonemapping and gamma correction
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomParams {
+    // brightest channel a pixel needs before it starts to glow
+    pub threshold: f32,
+    // scale of the blurred glow added back to the image
+    pub intensity: f32,
+    // standard deviation of the Gaussian blur in pixels
+    pub radius: f32,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.2,
+            radius: 4.0,
+        }
+    }
+}
+
+pub fn apply_bloom(buffer: &mut [Vec3], width: usize, height: usize, params: BloomParams) {
+    assert_eq!(buffer.len(), width * height);
+    if params.radius <= 0.0 || params.intensity <= 0.0 {
+        return;
+    }
+
+    // keep only the energy above the threshold
+    let bright: Vec<Vec3> = buffer
+        .iter()
+        .map(|&c| {
+            let excess = c.max_element() - params.threshold;
+            if excess > 0.0 {
+                c * (excess / c.max_element())
+            } else {
+                Vec3::ZERO
+            }
+        })
+        .collect();
+
+    let kernel = gaussian_kernel(params.radius);
+    let half = kernel.len() as isize / 2;
+
+    // separable blur, horizontal then vertical, clamping at the image edges
+    let mut horizontal = vec![Vec3::ZERO; bright.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, &weight)| {
+                    let sx = (x as isize + k as isize - half).clamp(0, width as isize - 1) as usize;
+                    bright[y * width + sx] * weight
+                })
+                .sum();
+        }
+    }
+    for y in 0..height {
+        for x in 0..width {
+            let glow: Vec3 = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, &weight)| {
+                    let sy =
+                        (y as isize + k as isize - half).clamp(0, height as isize - 1) as usize;
+                    horizontal[sy * width + x] * weight
+                })
+                .sum();
+            buffer[y * width + x] += glow * params.intensity;
+        }
+    }
+}
+
+// normalized 1D Gaussian covering three standard deviations either side
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let half = (3.0 * sigma).ceil() as isize;
+    let weights: Vec<f32> = (-half..=half)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+// darken towards the corners, by `strength` at the corners falling off with squared radius
+pub fn vignette(buffer: &mut [Vec3], width: usize, height: usize, strength: f32) {
+    assert_eq!(buffer.len(), width * height);
+    let center = (width as f32 / 2.0, height as f32 / 2.0);
+    let corner_distance_squared = center.0 * center.0 + center.1 * center.1;
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x as f32 + 0.5 - center.0;
+            let dy = y as f32 + 0.5 - center.1;
+            let r2 = (dx * dx + dy * dy) / corner_distance_squared;
+            buffer[y * width + x] *= (1.0 - strength * r2).max(0.0);
+        }
+    }
+}
+
+// strongest chromatic aberration allowed. at 1 the blue channel would be scaled to nothing
+pub const MAX_CHROMATIC_ABERRATION: f32 = 0.5;
+
+// scale the red channel outwards and the blue inwards from the image center, by `strength`
+// as a fraction of the distance to the center, clamped to 0..=MAX_CHROMATIC_ABERRATION
+pub fn chromatic_aberration(buffer: &mut [Vec3], width: usize, height: usize, strength: f32) {
+    assert_eq!(buffer.len(), width * height);
+    let strength = strength.clamp(0.0, MAX_CHROMATIC_ABERRATION);
+    let source = buffer.to_vec();
+    let center = (width as f32 / 2.0, height as f32 / 2.0);
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x as f32 + 0.5 - center.0;
+            let dy = y as f32 + 0.5 - center.1;
+            // a channel magnified by `scale` shows the source content from closer to the center
+            let sample = |scale: f32| {
+                bilinear(
+                    &source,
+                    width,
+                    height,
+                    center.0 + dx / scale - 0.5,
+                    center.1 + dy / scale - 0.5,
+                )
+            };
+            let pixel = &mut buffer[y * width + x];
+            pixel.x = sample(1.0 + strength).x;
+            pixel.z = sample(1.0 - strength).z;
+        }
+    }
+}
+
+// sample at continuous pixel coordinates, clamping at the image edges
+fn bilinear(buffer: &[Vec3], width: usize, height: usize, x: f32, y: f32) -> Vec3 {
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x.fract(), y.fract());
+
+    let top = buffer[y0 * width + x0].lerp(buffer[y0 * width + x1], tx);
+    let bottom = buffer[y1 * width + x0].lerp(buffer[y1 * width + x1], tx);
+    top.lerp(bottom, ty)
+}
@@ -7,12 +7,17 @@ pub struct Ray {
 }
 
 impl Ray {
+    /// A zero-length (or non-finite) `direction` produces a degenerate ray with a zero
+    /// direction instead of NaNs. Degenerate rays never hit anything.
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
         Self {
             origin,
-            direction: direction.normalize(),
+            direction: direction.normalize_or_zero(),
         }
     }
+    pub fn is_degenerate(&self) -> bool {
+        self.direction == Vec3::ZERO
+    }
     pub fn origin(&self) -> Vec3 {
         self.origin
     }
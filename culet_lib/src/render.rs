@@ -1,39 +1,271 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    mpsc::*,
-    Arc,
+use std::{
+    f32::consts::{FRAC_1_PI, PI, TAU},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::*,
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use bytemuck::{Pod, Zeroable};
-use glam::{vec3, Vec3};
+use glam::{vec2, vec3, Vec2, Vec3};
+use image::{ImageResult, RgbImage, RgbaImage};
 use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
-use rayon::ThreadPoolBuilder;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 
+#[cfg(feature = "caustics")]
+use crate::photon_map::PhotonMap;
 use crate::{
     camera::Camera,
-    hittable::Hittable,
-    material::{Material, DEFAULT_GEM_COLOR, DEFAULT_GEM_RI, DEFAULT_GEM_DISPERSION},
+    checkpoint::Checkpoint,
+    color::{linear_to_srgb, SpectralAccumulator, WAVELENGTH_MAX, WAVELENGTH_MIN},
+    hittable::{HitInfo, Hittable},
+    material::{Material, DEFAULT_GEM_COLOR, DEFAULT_GEM_DISPERSION, DEFAULT_GEM_RI},
+    post::{apply_bloom, chromatic_aberration, vignette, BloomParams, MAX_CHROMATIC_ABERRATION},
     ray::Ray,
+    sampler::{Sampler, SamplerKind},
     scene::Scene,
+    texture::sample_bilinear,
 };
 
 pub enum RenderMsg {
-    Pixel { x: u32, y: u32, color: Vec3 },
+    // `alpha` is the share of the pixel's primary rays that hit geometry, counted from the
+    // same rays that produced `color`
+    Pixel {
+        x: u32,
+        y: u32,
+        color: Vec3,
+        alpha: f32,
+    },
     Abort,
+    // every pixel has been sent, never sent for an aborted render
+    Done,
 }
 
+/// Auxiliary output buffers, recorded at the first hit of each pixel's primary ray.
+/// All buffers are row-major with `image_width * image_height` entries.
+#[derive(Clone, Debug, Default)]
+pub struct Aovs {
+    pub color: Vec<Vec3>,
+    pub albedo: Vec<Vec3>,
+    pub normal: Vec<Vec3>,
+    pub depth: Vec<f32>,
+}
+
+// error of a render against a reference after increasing sample counts, from
+// render_with_reference
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConvergenceReport {
+    // (samples per pixel, root mean squared error over every channel of every pixel)
+    pub points: Vec<(usize, f32)>,
+}
+
+impl ConvergenceReport {
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut csv = String::from("samples,rmse\n");
+        for (samples, rmse) in self.points.iter() {
+            csv += &format!("{samples},{rmse}\n");
+        }
+        std::fs::write(path, csv)
+    }
+}
+
+// bookkeeping gathered while tracing a single primary ray
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TraceStats {
+    // rays traced, counting every reflection and refraction branch
+    pub rays: u32,
+    // refractive surface hits where all light was reflected
+    pub total_internal_reflections: u32,
+    // whether the primary ray hit anything within the clip range
+    pub primary_hit: bool,
+}
+
+// totals for the most recent render_streaming call, shared by clones of the options
+#[derive(Debug, Default)]
+pub struct RenderStats {
+    rays: AtomicU64,
+    total_internal_reflections: AtomicU64,
+}
+
+impl RenderStats {
+    fn reset(&self) {
+        self.rays.store(0, Ordering::Relaxed);
+        self.total_internal_reflections.store(0, Ordering::Relaxed);
+    }
+    fn add(&self, stats: TraceStats) {
+        self.rays.fetch_add(stats.rays as u64, Ordering::Relaxed);
+        self.total_internal_reflections
+            .fetch_add(stats.total_internal_reflections as u64, Ordering::Relaxed);
+    }
+}
+
+// branches of the ray tree contributing less than this to every channel are dropped
+const MIN_THROUGHPUT: f32 = 1e-4;
+
+// side of the pixel tiles whose primary rays form one packet with `ray_packets`
+pub const PACKET_TILE: usize = 4;
+
+// how often the time limit checks whether the render has already finished
+const TIME_LIMIT_POLL: Duration = Duration::from_millis(50);
+
+// a refractive volume a path can be travelling through
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Medium {
+    refractive_index: f32,
+    absorption: Vec3,
+    fluorescence: Vec3,
+    inclusion_density: f32,
+    inclusion_color: Vec3,
+}
+
+impl Medium {
+    // fluorescence emitted along `distance` inside the medium, as seen from the start of the
+    // segment through the medium's own absorption
+    fn glow(&self, distance: f32) -> Vec3 {
+        Vec3::from_array(self.absorption.to_array().map(|a| {
+            if a > 1e-6 {
+                (1.0 - (-a * distance).exp()) / a
+            } else {
+                distance
+            }
+        })) * self.fluorescence
+    }
+
+    // free-flight distance to the next inclusion, infinite in a clean medium
+    fn scatter_distance(&self, rng: &mut impl Rng) -> f32 {
+        if self.inclusion_density > 0.0 {
+            -(1.0 - rng.gen::<f32>()).ln() / self.inclusion_density
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+// paths nested deeper than this stay in their innermost tracked medium
+const MAX_NESTED_MEDIA: usize = 4;
+
+// the media a path is inside, innermost last. crossing a front face enters the mesh's medium
+// and crossing a back face leaves it, so an inclusion inside a stone is bounded by the
+// stone's index rather than the exterior's
+#[derive(Copy, Clone, Debug, Default)]
+struct MediumStack {
+    media: [Medium; MAX_NESTED_MEDIA],
+    len: usize,
+}
+
+impl MediumStack {
+    fn innermost(&self) -> Option<Medium> {
+        self.len.checked_sub(1).map(|i| self.media[i])
+    }
+    fn refractive_index(&self, exterior_ri: f32) -> f32 {
+        self.innermost()
+            .map_or(exterior_ri, |medium| medium.refractive_index)
+    }
+    fn entered(mut self, medium: Medium) -> Self {
+        if self.len < MAX_NESTED_MEDIA {
+            self.media[self.len] = medium;
+            self.len += 1;
+        }
+        self
+    }
+    // overlapping volumes can be left in a different order than they were entered
+    fn left(mut self, medium: Medium) -> Self {
+        if let Some(i) = self.media[..self.len].iter().rposition(|m| *m == medium) {
+            self.media.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+        self
+    }
+}
+
+// a pending branch of the ray tree, weighted by its contribution to the pixel
+#[derive(Copy, Clone, Debug)]
+struct PathSegment {
+    ray: Ray,
+    throughput: Vec3,
+    bounces_left: usize,
+    media: MediumStack,
+}
+
+impl PathSegment {
+    // past `rr_start_bounce` branches are randomly terminated with a probability based on
+    // their throughput (Russian roulette), survivors are reweighted to keep the estimate unbiased
+    fn push_to(
+        mut self,
+        stack: &mut Vec<PathSegment>,
+        bounce: usize,
+        rr_start_bounce: usize,
+        rng: &mut impl Rng,
+    ) {
+        if self.throughput.max_element() < MIN_THROUGHPUT {
+            return;
+        }
+        if bounce >= rr_start_bounce {
+            let survival = self.throughput.max_element().min(1.0);
+            if rng.gen::<f32>() >= survival {
+                return;
+            }
+            self.throughput /= survival;
+        }
+        stack.push(self);
+    }
+}
+
+// stops or pauses the workers of a streaming render.
+// paused workers finish their current pixel and then sleep. a consumer that just stops
+// receiving leaves them tracing until channel_capacity pixels are queued, then they block too
 #[derive(Clone, Debug)]
-pub struct AbortSignal(Arc<AtomicBool>);
+pub struct AbortSignal(Arc<SignalState>);
+
+#[derive(Debug, Default)]
+struct SignalState {
+    aborted: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    // messages sent but not received yet, workers wait on `dequeued` while channel_capacity
+    // of them are queued
+    queued: Mutex<usize>,
+    dequeued: Condvar,
+    receiver_dropped: AtomicBool,
+}
 
 impl AbortSignal {
     pub fn new() -> Self {
-        Self(Arc::new(AtomicBool::new(false)))
+        Self(Arc::default())
     }
     pub fn abort(&self) {
-        self.0.store(true, Ordering::Relaxed)
+        self.0.aborted.store(true, Ordering::Relaxed);
+        // paused workers and ones waiting for room in the channel have to wake up to notice
+        {
+            let _paused = self.0.paused.lock().unwrap();
+            self.0.resumed.notify_all();
+        }
+        let _queued = self.0.queued.lock().unwrap();
+        self.0.dequeued.notify_all();
     }
     pub fn is_aborted(&self) -> bool {
-        self.0.load(Ordering::Relaxed)
+        self.0.aborted.load(Ordering::Relaxed)
+    }
+    pub fn pause(&self) {
+        *self.0.paused.lock().unwrap() = true;
+    }
+    pub fn resume(&self) {
+        *self.0.paused.lock().unwrap() = false;
+        self.0.resumed.notify_all();
+    }
+    pub fn is_paused(&self) -> bool {
+        *self.0.paused.lock().unwrap()
+    }
+    // blocks the calling worker until resumed or aborted
+    fn wait_while_paused(&self) {
+        let mut paused = self.0.paused.lock().unwrap();
+        while *paused && !self.is_aborted() {
+            paused = self.0.resumed.wait(paused).unwrap();
+        }
     }
 }
 
@@ -43,61 +275,330 @@ impl Default for AbortSignal {
     }
 }
 
+// receiving end of render_streaming, used like an mpsc Receiver. every message taken makes
+// room for the workers to queue another, and dropping it stops them
+#[derive(Debug)]
+pub struct RenderReceiver {
+    rx: Receiver<RenderMsg>,
+    signal: AbortSignal,
+}
+
+impl RenderReceiver {
+    pub fn recv(&self) -> Result<RenderMsg, RecvError> {
+        self.rx.recv().map(|message| self.taken(message))
+    }
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<RenderMsg, RecvTimeoutError> {
+        self.rx
+            .recv_timeout(timeout)
+            .map(|message| self.taken(message))
+    }
+    pub fn try_recv(&self) -> Result<RenderMsg, TryRecvError> {
+        self.rx.try_recv().map(|message| self.taken(message))
+    }
+    // blocks for each message until the workers hang up
+    pub fn iter(&self) -> impl Iterator<Item = RenderMsg> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+    fn taken(&self, message: RenderMsg) -> RenderMsg {
+        let mut queued = self.signal.0.queued.lock().unwrap();
+        *queued = queued.saturating_sub(1);
+        self.signal.0.dequeued.notify_one();
+        message
+    }
+}
+
+impl Drop for RenderReceiver {
+    fn drop(&mut self) {
+        self.signal
+            .0
+            .receiver_dropped
+            .store(true, Ordering::Relaxed);
+        let _queued = self.signal.0.queued.lock().unwrap();
+        self.signal.0.dequeued.notify_all();
+    }
+}
+
+impl IntoIterator for RenderReceiver {
+    type Item = RenderMsg;
+    type IntoIter = RenderIter;
+
+    fn into_iter(self) -> RenderIter {
+        RenderIter(self)
+    }
+}
+
+// owning iterator over a RenderReceiver's messages
+#[derive(Debug)]
+pub struct RenderIter(RenderReceiver);
+
+impl Iterator for RenderIter {
+    type Item = RenderMsg;
+
+    fn next(&mut self) -> Option<RenderMsg> {
+        self.0.recv().ok()
+    }
+}
+
+// returned by render_streaming. it controls the render like the AbortSignal it derefs to, and
+// also tracks the workers, so callers don't have to infer completion from the channel
+#[derive(Clone, Debug)]
+pub struct RenderHandle {
+    abort_signal: AbortSignal,
+    // workers that have stopped, after their last pixel or after noticing an abort
+    stopped_workers: Arc<AtomicUsize>,
+    workers: usize,
+}
+
+impl RenderHandle {
+    fn new(workers: usize) -> Self {
+        Self {
+            abort_signal: AbortSignal::new(),
+            stopped_workers: Arc::default(),
+            workers,
+        }
+    }
+    pub fn abort_signal(&self) -> AbortSignal {
+        self.abort_signal.clone()
+    }
+    // no worker is tracing any more. a render that wasn't aborted has queued Done by then
+    pub fn is_finished(&self) -> bool {
+        self.stopped_workers.load(Ordering::Acquire) >= self.workers
+    }
+}
+
+impl Deref for RenderHandle {
+    type Target = AbortSignal;
+
+    fn deref(&self) -> &AbortSignal {
+        &self.abort_signal
+    }
+}
+
+// what RenderOptions::sweep varies between its images
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SweepParam {
+    // refractive index of every refractive material in the scene
+    Ri,
+    Dispersion,
+    // multiplier on each mesh's refractive absorption color
+    AbsorptionScale,
+    LightIntensity,
+}
+
+impl SweepParam {
+    fn file_prefix(&self) -> &'static str {
+        match self {
+            Self::Ri => "ri",
+            Self::Dispersion => "dispersion",
+            Self::AbsorptionScale => "absorption",
+            Self::LightIntensity => "light",
+        }
+    }
+}
+
+// a distant disc light seen by rays leaving the scene, added on top of the lighting model
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sun {
+    // unit direction the sunlight travels in
+    pub direction: Vec3,
+    // in degrees, the real sun is about 0.27
+    pub angular_radius: f32,
+    pub color: Vec3,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum LightingModel {
     Isometric,
     Cosine,
+    // cosine raised to `sharpness`, 0 matches Isometric and 1 matches Cosine
+    Hemisphere { sharpness: f32 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShadingMode {
+    Full,
+    // color primary hits by their normal, back faces tinted red
+    Normals,
+    // color primary hits by barycentric coordinates, one channel per vertex
+    Barycentric,
+    // full render with the edges of each mesh's bounding box drawn over it
+    BoundingBoxes,
+}
+
+// short-range occlusion of the light reaching diffuse surfaces, e.g. the contact shadow
+// under a stone resting on a plane
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AmbientOcclusion {
+    // cosine-weighted hemisphere rays per diffuse hit
+    pub samples: usize,
+    // geometry further away than this doesn't occlude
+    pub radius: f32,
+}
+
+// how pixels map to primary ray directions
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Projection {
+    // the camera's frustum
+    #[default]
+    Pinhole,
+    // longitude across and latitude down the full image from the camera position, with the
+    // look direction in the center, for 2:1 images viewed in VR
+    Equirectangular,
 }
 
 #[derive(Clone, Debug)]
 pub struct RenderOptions {
     pub camera: Camera,
+    pub projection: Projection,
     pub scene: Arc<Scene>,
     pub image_width: usize,
     pub image_height: usize,
     pub samples_per_pixel: usize,
+    pub sampler: SamplerKind,
+    pub jitter_first_sample: bool,
+    // mixed into every sample's rng in render_streaming. renders with the same options and
+    // seed are identical, change it between frames that are meant to be averaged
+    pub seed: u64,
     pub max_bounces: usize,
+    pub rr_start_bounce: usize,
+    // trace the primary rays of PACKET_TILE x PACKET_TILE pixel tiles together, full
+    // shading only
+    pub ray_packets: bool,
+    // primary rays ignore hits closer than clip_near, every ray ignores hits beyond clip_far
+    pub clip_near: f32,
+    pub clip_far: f32,
     pub lighting_model: LightingModel,
+    pub shading_mode: ShadingMode,
     pub light_intensity: f32,
+    pub sun: Option<Sun>,
+    // excites the fluorescence of refractive materials
+    pub uv_light: bool,
+    // cone around the observer, in degrees, that their head blocks from the light, 0 disables
+    pub head_shadow_degrees: f32,
+    // relative depth or normal change between neighbouring primary hits that render_edge_aware
+    // treats as an edge
+    pub edge_threshold: f32,
+    pub direct_lighting: bool,
+    pub ao: Option<AmbientOcclusion>,
     pub background_color: Vec3,
+    // rays leaving the scene after a bounce see background_color instead of the lighting
+    // model, so the stone looks like a window onto the backdrop rather than a light meter
+    pub refracted_background: bool,
+    // files are written as RGBA with alpha from the coverage of the render's own primary
+    // rays, and the background made transparent by premultiplying the color
+    pub transparent_background: bool,
     pub gem_color: Vec3,
     pub gem_ri: f32,
     pub gem_dispersion: f32,
+    // refractive index of the medium around the meshes, e.g. 1.33 for a stone under water
+    pub exterior_ri: f32,
     pub threads: usize,
+    // reused by every render when set, instead of spawning `threads` new threads each time.
+    // `threads` is ignored while a pool is set
+    pub thread_pool: Option<Arc<ThreadPool>>,
+    // pixels render_streaming may queue ahead of the receiver before workers block
+    pub channel_capacity: usize,
+    // wall time after which a render is aborted, keeping the pixels finished so far
+    pub time_limit: Option<Duration>,
+    // render_checkpointed() saves its progress here every checkpoint_interval and once more
+    // when it ends. the other renders only resume from checkpoints, they don't write them
+    pub checkpoint_path: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    // samples traced by an earlier run, which rendering adds to rather than repeats
+    resume: Option<Arc<Checkpoint>>,
+    // half-open [x0, y0, x1, y1] pixel rectangle, the rest of the frame is left untouched
+    pub region: Option<[usize; 4]>,
+    pub bloom: Option<BloomParams>,
+    stats: Arc<RenderStats>,
+    // lens effects, 0 disables
+    pub vignette: f32,
+    pub chromatic_aberration: f32,
+    // images written to files are encoded with the sRGB transfer function, or with a pure
+    // power curve of this gamma when set. sRGB is the default rather than a 2.2 curve because
+    // it's what image viewers decode PNGs with. the two agree to within a level or two over
+    // most of the range, but near black a pure power curve lifts the shadows that sRGB keeps
+    // on its linear segment, so dark pavilions came out washed out
+    pub gamma: Option<f32>,
+    #[cfg(feature = "caustics")]
+    pub caustics: Option<Arc<PhotonMap>>,
+    // interactions a photon is followed through, separate from max_bounces since photons
+    // have to cross the stone before they can land on anything
+    #[cfg(feature = "caustics")]
+    pub photon_max_depth: usize,
 }
 
 impl RenderOptions {
     pub fn new() -> Self {
         Self {
             camera: Camera::default(),
+            projection: Projection::Pinhole,
             scene: Arc::new(Scene::empty()),
             image_width: 1280,
             image_height: 720,
             samples_per_pixel: 1,
+            sampler: SamplerKind::Uniform,
+            jitter_first_sample: false,
+            seed: 0,
             max_bounces: 1,
+            rr_start_bounce: 8,
+            ray_packets: false,
+            clip_near: 0.0,
+            clip_far: f32::INFINITY,
             lighting_model: LightingModel::Cosine,
+            shading_mode: ShadingMode::Full,
             light_intensity: 1.0,
+            sun: None,
+            uv_light: false,
+            head_shadow_degrees: 10.0,
+            edge_threshold: 0.1,
+            direct_lighting: true,
+            ao: None,
             background_color: Vec3::splat(0.1),
+            refracted_background: false,
+            transparent_background: false,
             gem_color: DEFAULT_GEM_COLOR,
             gem_ri: DEFAULT_GEM_RI,
             gem_dispersion: DEFAULT_GEM_DISPERSION,
+            exterior_ri: 1.0,
             threads: 1,
+            thread_pool: None,
+            channel_capacity: 65536,
+            time_limit: None,
+            checkpoint_path: None,
+            checkpoint_interval: Duration::from_secs(60),
+            resume: None,
+            region: None,
+            bloom: None,
+            stats: Arc::new(RenderStats::default()),
+            vignette: 0.0,
+            chromatic_aberration: 0.0,
+            gamma: None,
+            #[cfg(feature = "caustics")]
+            caustics: None,
+            #[cfg(feature = "caustics")]
+            photon_max_depth: 16,
         }
     }
     pub fn camera(mut self, camera: Camera) -> Self {
         self.camera = camera;
         self
     }
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
     pub fn scene(mut self, scene: Arc<Scene>) -> Self {
         self.scene = scene;
         self
     }
+    // zero-sized images are bumped to one pixel, set the fields directly to get an empty render
     pub fn image_width(mut self, image_width: usize) -> Self {
-        self.image_width = image_width;
+        self.image_width = image_width.max(1);
         self
     }
     pub fn image_height(mut self, image_height: usize) -> Self {
-        self.image_height = image_height;
+        self.image_height = image_height.max(1);
         self
     }
 
@@ -106,189 +607,1464 @@ impl RenderOptions {
         self
     }
 
+    pub fn clip_near(mut self, clip_near: f32) -> Self {
+        self.clip_near = clip_near;
+        self
+    }
+
+    pub fn clip_far(mut self, clip_far: f32) -> Self {
+        self.clip_far = clip_far;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // jitter the first sample of each pixel too, so 1spp frames are anti-aliased when
+    // accumulated. only affects SamplerKind::Uniform, the other samplers already place
+    // their first sample off center as part of their pattern.
+    // render_streaming seeds every sample from `seed` and its pixel and sample index, so the
+    // jitter is the same on every run and for any thread count or pixel order. frames meant
+    // to be accumulated need a different `seed` each, or they all jitter the same way.
+    // render_matte and render_bounce_counts keep their own per-pixel seeds
+    pub fn jitter_first_sample(mut self, jitter_first_sample: bool) -> Self {
+        self.jitter_first_sample = jitter_first_sample;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
     pub fn max_bounces(mut self, bounces: usize) -> Self {
         self.max_bounces = bounces;
         self
     }
 
+    pub fn rr_start_bounce(mut self, bounce: usize) -> Self {
+        self.rr_start_bounce = bounce;
+        self
+    }
+
+    pub fn ray_packets(mut self, ray_packets: bool) -> Self {
+        self.ray_packets = ray_packets;
+        self
+    }
+
+    pub fn lighting_model(mut self, lighting_model: LightingModel) -> Self {
+        self.lighting_model = lighting_model;
+        self
+    }
+
+    // `direction` is the way the light travels, so the sun is seen looking along -direction
+    pub fn sun(mut self, direction: Vec3, angular_radius: f32, color: Vec3) -> Self {
+        self.sun = Some(Sun {
+            direction: direction.normalize(),
+            angular_radius,
+            color,
+        });
+        self
+    }
+
+    pub fn uv_light(mut self, uv_light: bool) -> Self {
+        self.uv_light = uv_light;
+        self
+    }
+
+    pub fn head_shadow_degrees(mut self, head_shadow_degrees: f32) -> Self {
+        self.head_shadow_degrees = head_shadow_degrees;
+        self
+    }
+
+    pub fn edge_threshold(mut self, edge_threshold: f32) -> Self {
+        self.edge_threshold = edge_threshold;
+        self
+    }
+
+    pub fn direct_lighting(mut self, direct_lighting: bool) -> Self {
+        self.direct_lighting = direct_lighting;
+        self
+    }
+
+    pub fn ao(mut self, samples: usize, radius: f32) -> Self {
+        self.ao = Some(AmbientOcclusion { samples, radius });
+        self
+    }
+
+    pub fn shade_normals(mut self) -> Self {
+        self.shading_mode = ShadingMode::Normals;
+        self
+    }
+
+    pub fn shade_barycentric(mut self) -> Self {
+        self.shading_mode = ShadingMode::Barycentric;
+        self
+    }
+
+    pub fn shade_bounding_boxes(mut self) -> Self {
+        self.shading_mode = ShadingMode::BoundingBoxes;
+        self
+    }
+
     pub fn background_color(mut self, background_color: Vec3) -> Self {
         self.background_color = background_color;
         self
     }
 
+    pub fn refracted_background(mut self, refracted_background: bool) -> Self {
+        self.refracted_background = refracted_background;
+        self
+    }
+
+    pub fn transparent_background(mut self, transparent_background: bool) -> Self {
+        self.transparent_background = transparent_background;
+        self
+    }
+
+    pub fn exterior_ri(mut self, exterior_ri: f32) -> Self {
+        self.exterior_ri = exterior_ri;
+        self
+    }
+
     pub fn threads(mut self, threads: usize) -> Self {
         self.threads = threads;
         self
     }
 
-    pub fn render_streaming(&self) -> (Receiver<RenderMsg>, AbortSignal) {
-        let mut pixels: Vec<usize> = (0..self.image_width * self.image_height).collect();
+    pub fn thread_pool(mut self, thread_pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+
+    // build a pool of `threads` threads once and share it with every clone of these options,
+    // which saves respawning threads on each re-render in an interactive viewer
+    pub fn shared_thread_pool(self) -> Self {
+        let thread_pool = self.worker_pool();
+        self.thread_pool(thread_pool)
+    }
+
+    // the shared pool, or a fresh one for this render
+    fn worker_pool(&self) -> Arc<ThreadPool> {
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.clone(),
+            None => Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(self.threads)
+                    .build()
+                    .unwrap(),
+            ),
+        }
+    }
+
+    // at least 1, with no room to queue a pixel the workers would spin on try_send until the
+    // receiver happened to be waiting
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.channel_capacity = channel_capacity.max(1);
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.checkpoint_path = Some(path.into());
+        self.checkpoint_interval = interval;
+        self
+    }
+
+    // continue a render from a checkpoint it saved, set the image size first. raising
+    // samples_per_pixel since then adds the missing samples to every pixel
+    pub fn resume_from(mut self, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let checkpoint = Checkpoint::load(path)?;
+        let size = (checkpoint.width(), checkpoint.height());
+        if size != (self.image_width, self.image_height) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "checkpoint is {}x{} but the render is {}x{}",
+                    size.0, size.1, self.image_width, self.image_height
+                ),
+            ));
+        }
+        self.resume = Some(Arc::new(checkpoint));
+        Ok(self)
+    }
+
+    // the resumed checkpoint, unless the image has been resized since, e.g. by
+    // render_panorama on a clone
+    fn resumed(&self) -> Option<&Checkpoint> {
+        self.resume.as_deref().filter(|checkpoint| {
+            (checkpoint.width(), checkpoint.height()) == (self.image_width, self.image_height)
+        })
+    }
+
+    // samples of `pixel` already in the resumed checkpoint
+    fn resumed_samples(&self, pixel: usize) -> usize {
+        self.resumed()
+            .map_or(0, |checkpoint| checkpoint.samples(pixel) as usize)
+    }
+
+    pub fn region(mut self, x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        self.region = Some([x0, y0, x1, y1]);
+        self
+    }
+
+    pub fn full_frame(mut self) -> Self {
+        self.region = None;
+        self
+    }
+
+    pub fn bloom(mut self, bloom: BloomParams) -> Self {
+        self.bloom = Some(bloom);
+        self
+    }
+
+    pub fn vignette(mut self, strength: f32) -> Self {
+        self.vignette = strength;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = Some(gamma);
+        self
+    }
+
+    pub fn srgb(mut self) -> Self {
+        self.gamma = None;
+        self
+    }
+
+    // linear color to the encoding written to files
+    pub fn encode_color(&self, color: Vec3) -> Vec3 {
+        match self.gamma {
+            Some(gamma) => gamma_correct(color.clamp(Vec3::ZERO, Vec3::ONE), gamma),
+            None => linear_to_srgb(color),
+        }
+    }
+
+    // clamped to 0..=MAX_CHROMATIC_ABERRATION
+    pub fn chromatic_aberration(mut self, strength: f32) -> Self {
+        self.chromatic_aberration = strength.clamp(0.0, MAX_CHROMATIC_ABERRATION);
+        self
+    }
+
+    // total internal reflections during the last render_streaming call
+    pub fn last_render_tir_count(&self) -> u64 {
+        self.stats
+            .total_internal_reflections
+            .load(Ordering::Relaxed)
+    }
+
+    // total internal reflections per traced ray during the last render_streaming call, for
+    // comparing stones across resolutions and sample counts
+    pub fn last_render_tir_ratio(&self) -> f32 {
+        let rays = self.stats.rays.load(Ordering::Relaxed);
+        if rays == 0 {
+            return 0.0;
+        }
+        self.last_render_tir_count() as f32 / rays as f32
+    }
+
+    // image-space effects for a finished, row-major HDR buffer
+    pub fn post_process(&self, buffer: &mut [Vec3]) {
+        let (width, height) = (self.image_width, self.image_height);
+        if let Some(bloom) = self.bloom {
+            apply_bloom(buffer, width, height, bloom);
+        }
+        if self.chromatic_aberration != 0.0 {
+            chromatic_aberration(buffer, width, height, self.chromatic_aberration);
+        }
+        if self.vignette != 0.0 {
+            vignette(buffer, width, height, self.vignette);
+        }
+    }
+
+    // indices of the pixels to render, clamped to the image
+    fn region_pixels(&self) -> Vec<usize> {
+        let [x0, y0, x1, y1] = self
+            .region
+            .unwrap_or([0, 0, self.image_width, self.image_height]);
+        let (x1, y1) = (x1.min(self.image_width), y1.min(self.image_height));
+        (y0..y1)
+            .flat_map(|y| (x0..x1).map(move |x| y * self.image_width + x))
+            .collect()
+    }
+
+    // photons are shot into the current scene, so set the scene first
+    #[cfg(feature = "caustics")]
+    pub fn caustics(mut self, photon_count: usize, gather_radius: f32) -> Self {
+        let mut rng = SmallRng::seed_from_u64(photon_count as u64);
+        let photon_map = PhotonMap::emit(&self, photon_count, gather_radius, &mut rng);
+        self.caustics = Some(Arc::new(photon_map));
+        self
+    }
+
+    // set before `caustics`, which emits the photons
+    #[cfg(feature = "caustics")]
+    pub fn photon_max_depth(mut self, photon_max_depth: usize) -> Self {
+        self.photon_max_depth = photon_max_depth;
+        self
+    }
+
+    // the camera with its aspect ratio matched to the image, so renders aren't stretched
+    fn image_camera(&self) -> Camera {
+        let aspect_ratio = self.image_width.max(1) as f32 / self.image_height.max(1) as f32;
+        self.camera.aspect_ratio(aspect_ratio)
+    }
+
+    // offset of sample `index` within `pixel`, honouring jitter_first_sample
+    fn sample_offset(&self, pixel: usize, index: usize, rng: &mut impl Rng) -> Vec2 {
+        if index == 0 && self.jitter_first_sample && self.sampler == SamplerKind::Uniform {
+            return vec2(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+        }
+        self.sampler
+            .pixel_offset(pixel, index, self.samples_per_pixel, rng)
+    }
+
+    // the region's pixels grouped into the units render_streaming works on: PACKET_TILE
+    // square tiles when tracing packets, otherwise single pixels
+    fn pixel_groups(&self) -> Vec<Vec<usize>> {
+        let pixels: Vec<usize> = self
+            .region_pixels()
+            .into_iter()
+            .filter(|&pixel| self.resumed_samples(pixel) < self.samples_per_pixel)
+            .collect();
+        if !self.ray_packets || self.shading_mode != ShadingMode::Full {
+            return pixels.into_iter().map(|pixel| vec![pixel]).collect();
+        }
+        let mut tiles = std::collections::BTreeMap::<_, Vec<usize>>::new();
+        for pixel in pixels {
+            let (x, y) = (pixel % self.image_width, pixel / self.image_width);
+            tiles
+                .entry((y / PACKET_TILE, x / PACKET_TILE))
+                .or_default()
+                .push(pixel);
+        }
+        tiles.into_values().collect()
+    }
+
+    // primary rays ignore hits closer than this
+    fn primary_min_distance(&self) -> f32 {
+        self.scene.shadow_bias().max(self.clip_near)
+    }
+
+    // primary ray through pixel (x, y), `offset` from the pixel center in pixels
+    fn pixel_ray(&self, camera: &Camera, x: usize, y: usize, offset: Vec2) -> Ray {
+        let u = (x as f32 + 0.5 + offset.x) / self.image_width as f32;
+        let v = (y as f32 + 0.5 + offset.y) / self.image_height as f32;
+        match self.projection {
+            Projection::Pinhole => camera.ray(u, v),
+            Projection::Equirectangular => {
+                let (_, viewport_width, viewport_height) = camera.viewport();
+                let (right, up) = (viewport_width.normalize(), -viewport_height.normalize());
+                let longitude = (u - 0.5) * TAU;
+                let latitude = (0.5 - v) * PI;
+                let horizontal = right * longitude.sin() + camera.look_dir() * longitude.cos();
+                Ray::new(
+                    camera.position,
+                    horizontal * latitude.cos() + up * latitude.sin(),
+                )
+            }
+        }
+    }
+
+    pub fn render_streaming(&self) -> (RenderReceiver, RenderHandle) {
+        let mut groups = self.pixel_groups();
+
+        self.stats.reset();
+        // a zero-area image or region has no workers to report Done
+        if groups.is_empty() {
+            let (tx, rx) = channel();
+            let _ = tx.send(RenderMsg::Done);
+            let handle = RenderHandle::new(0);
+            let signal = handle.abort_signal();
+            return (RenderReceiver { rx, signal }, handle);
+        }
 
-        let (top_left, viewport_width, viewport_height) = self.camera.viewport();
-        let pixel_x_delta = viewport_width / self.image_width as f32;
-        let pixel_y_delta = viewport_height / self.image_height as f32;
+        let camera = self.image_camera();
 
-        let mut rng = SmallRng::from_entropy();
-        pixels.shuffle(&mut rng);
+        // only decides the order pixels arrive in, every sample seeds its own rng
+        groups.shuffle(&mut SmallRng::from_entropy());
 
+        // unbounded underneath, send_unless_aborted keeps it to channel_capacity
         let (tx, rx) = channel();
 
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(self.threads)
-            .build()
-            .unwrap();
-        let abort_signal = AbortSignal::new();
+        let thread_pool = self.worker_pool();
+
+        // one chunk of shuffled pixels per worker thread, each spawned as a single task
+        let chunk_size = groups
+            .len()
+            .div_ceil(thread_pool.current_num_threads())
+            .max(1);
+        let chunks = groups.chunks(chunk_size);
+        let remaining_chunks = Arc::new(AtomicUsize::new(chunks.len()));
+        let handle = RenderHandle::new(chunks.len());
+        let abort_signal = handle.abort_signal();
 
-        pixels.chunks(self.threads).for_each(|chunk| {
-            let mut rng = SmallRng::seed_from_u64(0x123456789ABCDEF);
+        chunks.for_each(|chunk| {
             let tx = tx.clone();
             let options = self.clone();
             let chunk = chunk.to_vec();
             let abort_signal = abort_signal.clone();
+            let remaining_chunks = remaining_chunks.clone();
+            let stopped_workers = handle.stopped_workers.clone();
 
             thread_pool.spawn(move || {
-                'pixel: for i in chunk {
+                'group: for group in chunk {
+                    abort_signal.wait_while_paused();
                     #[cfg(puffin)]
                     puffin::GlobalProfiler::lock().new_frame();
-                    let x = i % options.image_width;
-                    let y = i / options.image_width;
-
-                    // if (x, y) == (200, 200) {
-                    //     dbg!((x, y));
-                    // }
-                    let mut pixel = Vec3::default();
-                    for i in 0..options.samples_per_pixel {
+
+                    let mut pixels: Vec<Vec3> = group
+                        .iter()
+                        .map(|&i_pixel| {
+                            options
+                                .resumed()
+                                .map_or(Vec3::ZERO, |checkpoint| checkpoint.sum(i_pixel))
+                        })
+                        .collect();
+                    // primary rays that hit geometry, for the pixel's alpha
+                    let mut hits: Vec<u32> = group
+                        .iter()
+                        .map(|&i_pixel| {
+                            options
+                                .resumed()
+                                .map_or(0, |checkpoint| checkpoint.hits(i_pixel))
+                        })
+                        .collect();
+                    let first_sample = group
+                        .iter()
+                        .map(|&i_pixel| options.resumed_samples(i_pixel))
+                        .min()
+                        .unwrap_or(0);
+                    for i in first_sample..options.samples_per_pixel {
                         if abort_signal.is_aborted() {
-                            break 'pixel;
+                            break 'group;
                         }
-                        let mut pixel_position = top_left
-                            + (x as f32 + 0.5) * pixel_x_delta
-                            + (y as f32 + 0.5) * pixel_y_delta;
-                        if i != 0 {
-                            let x_jitter = rng.gen_range(-0.5..0.5);
-                            let y_jitter = rng.gen_range(-0.5..0.5);
-                            pixel_position += x_jitter * pixel_x_delta + y_jitter * pixel_y_delta;
+                        // resumed pixels skip the samples their checkpoint already holds
+                        let live: Vec<usize> = (0..group.len())
+                            .filter(|&k| i >= options.resumed_samples(group[k]))
+                            .collect();
+                        let mut rngs: Vec<SmallRng> = live
+                            .iter()
+                            .map(|&k| sample_rng(options.seed, group[k], i))
+                            .collect();
+                        let rays: Vec<Ray> = live
+                            .iter()
+                            .zip(rngs.iter_mut())
+                            .map(|(&k, rng)| {
+                                let i_pixel = group[k];
+                                let x = i_pixel % options.image_width;
+                                let y = i_pixel / options.image_width;
+                                let offset = options.sample_offset(i_pixel, i, rng);
+                                options.pixel_ray(&camera, x, y, offset)
+                            })
+                            .collect();
+                        // single pixels trace their primary ray on their own
+                        let primary_hits = (rays.len() > 1).then(|| {
+                            options.scene.hit_packet_within(
+                                &rays,
+                                options.primary_min_distance(),
+                                options.clip_far,
+                            )
+                        });
+                        for (j, ((&k, ray), rng)) in live
+                            .iter()
+                            .zip(rays.iter())
+                            .zip(rngs.iter_mut())
+                            .enumerate()
+                        {
+                            let (color, stats) = match &primary_hits {
+                                Some(hits) => {
+                                    options.trace_path(ray, Some(hits[j]), options.max_bounces, rng)
+                                }
+                                None => options.shade_with_stats(ray, rng),
+                            };
+                            options.stats.add(stats);
+                            pixels[k] += finite_sample(color);
+                            hits[k] += stats.primary_hit as u32;
+                        }
+                    }
+                    for ((&i_pixel, pixel), hits) in group.iter().zip(pixels).zip(hits) {
+                        let message = RenderMsg::Pixel {
+                            x: (i_pixel % options.image_width) as u32,
+                            y: (i_pixel / options.image_width) as u32,
+                            color: pixel / options.samples_per_pixel as f32,
+                            alpha: hits as f32 / options.samples_per_pixel as f32,
+                        };
+                        if !send_unless_aborted(
+                            &tx,
+                            message,
+                            &abort_signal,
+                            options.channel_capacity,
+                        ) {
+                            break 'group;
                         }
-                        let ray = Ray::new(
-                            options.camera.position,
-                            pixel_position - options.camera.position,
-                        );
-                        pixel += options.trace(&ray, options.max_bounces);
                     }
-                    let _ = tx.send(RenderMsg::Pixel {
-                        x: x as u32,
-                        y: y as u32,
-                        color: pixel / options.samples_per_pixel as f32,
-                    });
                 }
+
+                // aborted chunks never count as finished, so Done can't follow an abort
+                if !abort_signal.is_aborted()
+                    && remaining_chunks.fetch_sub(1, Ordering::AcqRel) == 1
+                {
+                    send_unless_aborted(
+                        &tx,
+                        RenderMsg::Done,
+                        &abort_signal,
+                        options.channel_capacity,
+                    );
+                }
+                stopped_workers.fetch_add(1, Ordering::Release);
             });
         });
 
-        (rx, abort_signal)
+        if let Some(time_limit) = self.time_limit {
+            let handle = handle.clone();
+            let deadline = Instant::now() + time_limit;
+            std::thread::spawn(move || {
+                // exits early once the render ends by itself, rather than sleeping out the limit
+                while !handle.is_finished() && !handle.is_aborted() {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        handle.abort();
+                        break;
+                    }
+                    std::thread::sleep((deadline - now).min(TIME_LIMIT_POLL));
+                }
+            });
+        }
+
+        (rx, handle)
     }
 
-    pub fn trace(&self, ray: &Ray, max_bounces: usize) -> Vec3 {
-        #[cfg(puffin)]
-        puffin::profile_function!();
-        match self.scene.hit_point(ray, 1e-5) {
-            Some(info) => {
-                if max_bounces == 0 {
-                    return info.material.color() * 0.0;
+    // stream a render into `path`, rewriting the image every `every` until it is finished
+    pub fn render_to_file_progressive(
+        &self,
+        path: impl AsRef<Path>,
+        every: Duration,
+    ) -> ImageResult<()> {
+        let path = path.as_ref();
+        let (rx, _handle) = self.render_streaming();
+        let mut buffer = vec![Vec3::ZERO; self.image_width * self.image_height];
+        let mut coverage = vec![0.0; buffer.len()];
+        let mut next_write = Instant::now() + every;
+
+        loop {
+            match rx.recv_timeout(next_write.saturating_duration_since(Instant::now())) {
+                Ok(RenderMsg::Pixel { x, y, color, alpha }) => {
+                    let pixel = y as usize * self.image_width + x as usize;
+                    buffer[pixel] = color;
+                    coverage[pixel] = alpha;
                 }
-                match info.material {
-                    Material::Refractive {
-                        color,
-                        refractive_index,
-                        dispersion: _,
-                    } => {
-                        let (normal, eta_i, eta_t) = if info.front_face {
-                            (info.normal, 1.0, refractive_index)
-                        } else {
-                            (-info.normal, refractive_index, 1.0)
-                        };
-                        let reflection_ratio = fresnel(ray.direction(), normal, eta_i, eta_t);
-
-                        let exiting_pavilion =
-                            !info.front_face && normal.dot(vec3(0.0, 0.0, 1.0)) > 0.0;
-                        // color from refraction ray
-                        let refraction_color = if reflection_ratio < 1.0 && !exiting_pavilion {
-                            #[cfg(puffin)]
-                            puffin::profile_scope!("Refraction Ray");
-                            let ri_ratio = if info.front_face {
-                                1.0 / refractive_index
-                            } else {
-                                refractive_index
-                            };
+                Ok(RenderMsg::Done) | Err(RecvTimeoutError::Disconnected) => break,
+                Ok(RenderMsg::Abort) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    self.write_image_atomically(path, &buffer, &coverage)?;
+                    next_write = Instant::now() + every;
+                }
+            }
+        }
 
-                            debug_assert!(
-                                ray.direction().is_normalized() && normal.is_normalized()
-                            );
-                            let cos_1 = -ray.direction().dot(normal);
+        self.write_image_atomically(path, &buffer, &coverage)
+    }
 
-                            let out_perp = ri_ratio * (ray.direction() + cos_1 * normal);
-                            let out_parallel =
-                                normal * -(1.0 - out_perp.length_squared().min(1.0)).sqrt();
+    // blocking render of the whole region, row-major. with a time_limit, pixels that weren't
+    // finished in time are left black
+    pub fn render(&self) -> Vec<Vec3> {
+        self.render_with_coverage().0
+    }
 
-                            let out_direction = out_perp + out_parallel;
-                            let out_origin = info.position;
+    // render() that also saves checkpoints to checkpoint_path. the first checkpoint that
+    // can't be written stops the render, rather than finding out hours later that there is
+    // nothing to resume from
+    pub fn render_checkpointed(&self) -> std::io::Result<Vec<Vec3>> {
+        let (buffer, _) = self.render_saving(self.checkpoint_path.as_deref())?;
+        Ok(buffer)
+    }
 
-                            self.trace(&Ray::new(out_origin, out_direction), max_bounces - 1)
-                        } else {
-                            Vec3::splat(0.0)
-                        };
+    // render() along with each pixel's alpha from the same primary rays, see RenderMsg::Pixel.
+    // pixels that weren't finished are fully transparent
+    fn render_with_coverage(&self) -> (Vec<Vec3>, Vec<f32>) {
+        match self.render_saving(None) {
+            Ok(render) => render,
+            Err(_) => unreachable!("nothing is saved without a checkpoint path"),
+        }
+    }
 
-                        // color from reflection ray
-                        let reflection_color = {
-                            #[cfg(puffin)]
-                            puffin::profile_scope!("Reflection ray");
-                            let out_direction = (ray.direction()
-                                - 2.0 * ray.direction().dot(normal) * normal)
-                                .normalize();
-                            let out_origin = info.position;
+    fn render_saving(
+        &self,
+        checkpoint_path: Option<&Path>,
+    ) -> std::io::Result<(Vec<Vec3>, Vec<f32>)> {
+        let (rx, _handle) = self.render_streaming();
+        let mut progress = match self.resumed() {
+            Some(checkpoint) => checkpoint.clone(),
+            None => Checkpoint::new(self.image_width, self.image_height),
+        };
+        let mut last_checkpoint = Instant::now();
+        for message in rx {
+            match message {
+                RenderMsg::Pixel { x, y, color, alpha } => {
+                    let pixel = y as usize * self.image_width + x as usize;
+                    progress.record(pixel, color, alpha, self.samples_per_pixel as u32);
+                }
+                RenderMsg::Done => break,
+                RenderMsg::Abort => {}
+            }
+            if let Some(path) = checkpoint_path {
+                if last_checkpoint.elapsed() >= self.checkpoint_interval {
+                    progress.save(path)?;
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+        if let Some(path) = checkpoint_path {
+            progress.save(path)?;
+        }
+        Ok((0..self.image_width * self.image_height)
+            .map(|pixel| (progress.color(pixel), progress.alpha(pixel)))
+            .unzip())
+    }
 
-                            self.trace(&Ray::new(out_origin, out_direction), max_bounces - 1)
-                        };
+    // full sphere of directions around the camera position, as a width x height
+    // equirectangular image (normally width = 2 * height)
+    pub fn render_panorama(&self, width: usize, height: usize) -> Vec<Vec3> {
+        self.clone()
+            .image_width(width)
+            .image_height(height)
+            .full_frame()
+            .projection(Projection::Equirectangular)
+            .render()
+    }
 
-                        let subcolor = reflection_ratio * reflection_color
-                            + (1.0 - reflection_ratio) * refraction_color;
+    // left and right eye renders from cameras `eye_separation` apart along the camera's
+    // right vector, both looking the same way
+    pub fn render_stereo(&self, eye_separation: f32) -> (Vec<Vec3>, Vec<Vec3>) {
+        let (_, viewport_width, _) = self.camera.viewport();
+        let offset = viewport_width.normalize_or_zero() * eye_separation / 2.0;
+        let eye = |offset: Vec3| {
+            let mut camera = self.camera;
+            camera.position += offset;
+            self.clone().camera(camera).render()
+        };
+        (eye(-offset), eye(offset))
+    }
 
-                        // subcolor
+    // red/cyan anaglyph of a render_stereo pair, post-processed and encoded like written
+    // images: red from the left eye, green and blue from the right
+    pub fn compose_anaglyph(&self, left: &[Vec3], right: &[Vec3]) -> RgbImage {
+        let (mut left, mut right) = (left.to_vec(), right.to_vec());
+        self.post_process(&mut left);
+        self.post_process(&mut right);
+        RgbImage::from_fn(self.image_width as u32, self.image_height as u32, |x, y| {
+            let i = y as usize * self.image_width + x as usize;
+            let (l, r) = (self.encode_color(left[i]), self.encode_color(right[i]));
+            let [r, g, b] = (vec3(l.x, r.y, r.z) * 255.0).round().to_array();
+            image::Rgb([r as u8, g as u8, b as u8])
+        })
+    }
 
-                        if !info.front_face {
-                            // Beer's law: attenuate color through a translucent medium
-                            subcolor * (-color * info.ray_distance).exp()
-                        } else {
-                            subcolor
+    // render one image per value of `param` into `out_dir`, named like `ri_2.417.png`, and
+    // return their paths. only materials change between images, so the scene's BVH is reused
+    pub fn sweep(
+        &self,
+        param: SweepParam,
+        values: &[f32],
+        out_dir: impl AsRef<Path>,
+    ) -> ImageResult<Vec<PathBuf>> {
+        let out_dir = out_dir.as_ref();
+        std::fs::create_dir_all(out_dir)?;
+
+        // absorption is scaled from each mesh's original color, not compounded
+        let base_absorption: Vec<Option<Vec3>> =
+            self.scene
+                .meshes()
+                .iter()
+                .map(|mesh| {
+                    mesh.triangle_slice()
+                        .iter()
+                        .find_map(|t| match t.material() {
+                            Material::Refractive { color, .. }
+                            | Material::ThinFilm { color, .. } => Some(color),
+                            _ => None,
+                        })
+                })
+                .collect();
+
+        let mut options = self.clone();
+        let mut paths = Vec::with_capacity(values.len());
+        for &value in values {
+            if param == SweepParam::LightIntensity {
+                options.light_intensity = value;
+            } else {
+                // the previous render's clones are gone by now, so after the first value this
+                // edits the copy in place
+                let scene = Arc::make_mut(&mut options.scene);
+                scene.modify_meshes(|meshes| {
+                    for (mesh, base) in meshes.iter_mut().zip(base_absorption.iter()) {
+                        match param {
+                            SweepParam::Ri => {
+                                mesh.apply_ri(value);
+                            }
+                            SweepParam::Dispersion => {
+                                mesh.apply_dispersion(value);
+                            }
+                            SweepParam::AbsorptionScale => {
+                                if let Some(base) = base {
+                                    mesh.apply_color(*base * value);
+                                }
+                            }
+                            SweepParam::LightIntensity => unreachable!(),
                         }
                     }
-                    Material::Diffuse { color: _ } => todo!(),
-                    Material::Light { color } => color,
+                });
+            }
+
+            let path = out_dir.join(format!("{}_{value}.png", param.file_prefix()));
+            let (buffer, coverage) = options.render_with_coverage();
+            options.write_image_atomically(&path, &buffer, &coverage)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    // write to a sibling temporary file and rename it over `path`, so readers never see a
+    // partially written image
+    fn write_image_atomically(
+        &self,
+        path: &Path,
+        buffer: &[Vec3],
+        coverage: &[f32],
+    ) -> ImageResult<()> {
+        let mut buffer = buffer.to_vec();
+        self.post_process(&mut buffer);
+
+        // keep the real extension last so the format is still inferred from it
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+        let temp_path = path.with_extension(format!("tmp.{extension}"));
+        if self.transparent_background {
+            self.compose_rgba(&buffer, coverage).save(&temp_path)?;
+        } else {
+            RgbImage::from_fn(self.image_width as u32, self.image_height as u32, |x, y| {
+                let color = self.encode_color(buffer[y as usize * self.image_width + x as usize]);
+                let [r, g, b] = (color * 255.0).round().to_array();
+                image::Rgb([r as u8, g as u8, b as u8])
+            })
+            .save(&temp_path)?;
+        }
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    // premultiplied RGBA from a render and its coverage, the alpha of its RenderMsg::Pixel
+    // messages or a render_matte of the same view. partly covered pixels have the
+    // background_color share of their color removed, so what's left is the gem's color
+    // already weighted by its coverage and the background is fully transparent
+    pub fn compose_rgba(&self, buffer: &[Vec3], matte: &[f32]) -> RgbaImage {
+        RgbaImage::from_fn(self.image_width as u32, self.image_height as u32, |x, y| {
+            let i = y as usize * self.image_width + x as usize;
+            let alpha = matte[i].clamp(0.0, 1.0);
+            let premultiplied = (buffer[i] - (1.0 - alpha) * self.background_color).max(Vec3::ZERO);
+            let color = self.encode_color(premultiplied);
+            let [r, g, b] = (color * 255.0).round().to_array();
+            image::Rgba([r as u8, g as u8, b as u8, (alpha * 255.0).round() as u8])
+        })
+    }
+
+    pub fn render_aovs(&self) -> Aovs {
+        let camera = self.image_camera();
+
+        let samples = self.par_pixels(|x, y| {
+            let mut rng = SmallRng::seed_from_u64((y * self.image_width + x) as u64);
+
+            // AOVs only come from the first hit of a ray through the pixel center
+            let center_ray = self.pixel_ray(&camera, x, y, Vec2::ZERO);
+            let (albedo, normal, depth) = self
+                .scene
+                .hit_point(&center_ray, self.scene.shadow_bias())
+                .map_or((Vec3::ZERO, Vec3::ZERO, f32::INFINITY), |info| {
+                    (
+                        info.material.color_at(info.position),
+                        info.normal,
+                        info.ray_distance,
+                    )
+                });
+
+            let mut pixel = Vec3::default();
+            for i in 0..self.samples_per_pixel {
+                let offset = self.sample_offset(y * self.image_width + x, i, &mut rng);
+                let ray = self.pixel_ray(&camera, x, y, offset);
+                pixel += finite_sample(self.shade(&ray, &mut rng));
+            }
+            (pixel / self.samples_per_pixel as f32, albedo, normal, depth)
+        });
+
+        let mut aovs = Aovs::default();
+        for (color, albedo, normal, depth) in samples {
+            aovs.color.push(color);
+            aovs.albedo.push(albedo);
+            aovs.normal.push(normal);
+            aovs.depth.push(depth);
+        }
+        aovs
+    }
+
+    // render the whole image sampling as usual, measuring the error against `reference`
+    // (row-major, image_width * image_height) at every power of two samples per pixel and at
+    // samples_per_pixel
+    pub fn render_with_reference(&self, reference: &[Vec3]) -> ConvergenceReport {
+        assert_eq!(
+            reference.len(),
+            self.image_width * self.image_height,
+            "reference must match the image size"
+        );
+        let camera = self.image_camera();
+        let checkpoints: Vec<usize> = (0..usize::BITS)
+            .map(|i| 1 << i)
+            .take_while(|&n| n < self.samples_per_pixel)
+            .chain([self.samples_per_pixel])
+            .collect();
+
+        // squared error per channel of each pixel at each checkpoint
+        let errors = self.par_pixels(|x, y| {
+            let i_pixel = y * self.image_width + x;
+            let mut rng = SmallRng::seed_from_u64(i_pixel as u64);
+            let mut pixel = Vec3::ZERO;
+            let mut errors = Vec::with_capacity(checkpoints.len());
+            for i in 0..self.samples_per_pixel {
+                let offset = self.sample_offset(i_pixel, i, &mut rng);
+                let ray = self.pixel_ray(&camera, x, y, offset);
+                pixel += finite_sample(self.shade(&ray, &mut rng));
+                if checkpoints.contains(&(i + 1)) {
+                    let error = pixel / (i + 1) as f32 - reference[i_pixel];
+                    errors.push(error * error);
+                }
+            }
+            errors
+        });
+
+        let channels = (3 * errors.len()).max(1) as f32;
+        let points = checkpoints
+            .iter()
+            .enumerate()
+            .map(|(k, &samples)| {
+                let total: f32 = errors
+                    .iter()
+                    .map(|pixel| pixel.get(k).map_or(0.0, |e| e.element_sum()))
+                    .sum();
+                (samples, (total / channels).sqrt())
+            })
+            .collect();
+        ConvergenceReport { points }
+    }
+
+    // one sample per pixel, plus `samples_per_pixel` jittered samples only on pixels whose
+    // primary hit differs from a neighbour's (facet and silhouette edges), row-major
+    pub fn render_edge_aware(&self) -> Vec<Vec3> {
+        let camera = self.image_camera();
+
+        let first_pass = self.par_pixels(|x, y| {
+            let mut rng = SmallRng::seed_from_u64((y * self.image_width + x) as u64);
+            let ray = self.pixel_ray(&camera, x, y, Vec2::ZERO);
+            let hit = self
+                .scene
+                .hit_point(&ray, self.scene.shadow_bias())
+                .map(|info| (info.normal, info.ray_distance));
+            (finite_sample(self.shade(&ray, &mut rng)), hit)
+        });
+
+        let is_edge = |x: usize, y: usize| {
+            let hit = first_pass[y * self.image_width + x].1;
+            [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|&(dx, dy)| {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx < 0
+                    || ny < 0
+                    || nx >= self.image_width as isize
+                    || ny >= self.image_height as isize
+                {
+                    return false;
                 }
+                let neighbour = first_pass[ny as usize * self.image_width + nx as usize].1;
+                primary_hits_differ(hit, neighbour, self.edge_threshold)
+            })
+        };
+
+        self.par_pixels(|x, y| {
+            let first_sample = first_pass[y * self.image_width + x].0;
+            if self.samples_per_pixel <= 1 || !is_edge(x, y) {
+                return first_sample;
+            }
+            // offset the seed so the extra samples don't repeat the first pass
+            let mut rng = SmallRng::seed_from_u64(
+                ((y * self.image_width + x) as u64) ^ 0x9E37_79B9_7F4A_7C15,
+            );
+            let mut pixel = first_sample;
+            for i in 1..self.samples_per_pixel {
+                let offset = self.sample_offset(y * self.image_width + x, i, &mut rng);
+                let ray = self.pixel_ray(&camera, x, y, offset);
+                pixel += finite_sample(self.shade(&ray, &mut rng));
             }
-            None => {
-                if max_bounces == self.max_bounces {
-                    self.background_color
+            pixel / self.samples_per_pixel as f32
+        })
+    }
+
+    // coverage of each pixel by geometry, row-major: the share of its primary rays that hit
+    // anything within the clip range, so edges come out anti-aliased with samples_per_pixel
+    // above 1. nothing is shaded, only the primary hit is traced
+    pub fn render_matte(&self) -> Vec<f32> {
+        let camera = self.image_camera();
+
+        self.par_pixels(|x, y| {
+            let pixel = y * self.image_width + x;
+            let mut rng = SmallRng::seed_from_u64(pixel as u64);
+            let hits = (0..self.samples_per_pixel)
+                .filter(|&i| {
+                    let offset = self.sample_offset(pixel, i, &mut rng);
+                    let ray = self.pixel_ray(&camera, x, y, offset);
+                    self.scene
+                        .hit_point_within(&ray, self.primary_min_distance(), self.clip_far)
+                        .is_some()
+                })
+                .count();
+            hits as f32 / self.samples_per_pixel.max(1) as f32
+        })
+    }
+
+    // number of rays traced for each pixel's unjittered primary ray, row-major
+    pub fn render_bounce_counts(&self) -> Vec<u32> {
+        let camera = self.image_camera();
+
+        self.par_pixels(|x, y| {
+            let mut rng = SmallRng::seed_from_u64((y * self.image_width + x) as u64);
+            let ray = self.pixel_ray(&camera, x, y, Vec2::ZERO);
+            let (_, stats) = self.trace_with_stats(&ray, self.max_bounces, &mut rng);
+            stats.rays
+        })
+    }
+
+    // evaluate `f(x, y)` for every pixel on the render's thread pool, collected row-major
+    fn par_pixels<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Send + Sync,
+    {
+        self.worker_pool().install(|| {
+            (0..self.image_width * self.image_height)
+                .into_par_iter()
+                .map(|i| f(i % self.image_width, i / self.image_width))
+                .collect()
+        })
+    }
+
+    // color of a single primary ray sample in the current shading mode
+    pub fn shade(&self, ray: &Ray, rng: &mut impl Rng) -> Vec3 {
+        self.shade_with_stats(ray, rng).0
+    }
+
+    // debug shading modes don't trace paths and only report whether the primary ray hit
+    pub fn shade_with_stats(&self, ray: &Ray, rng: &mut impl Rng) -> (Vec3, TraceStats) {
+        let (color, hit) = match self.shading_mode {
+            ShadingMode::Full => return self.trace_with_stats(ray, self.max_bounces, rng),
+            ShadingMode::Normals => match self.scene.hit_point(ray, self.scene.shadow_bias()) {
+                Some(info) => {
+                    let color = info.normal * 0.5 + 0.5;
+                    if info.front_face {
+                        (color, true)
+                    } else {
+                        (color.lerp(vec3(1.0, 0.0, 0.0), 0.5), true)
+                    }
+                }
+                None => (self.background_color, false),
+            },
+            ShadingMode::Barycentric => match self.scene.hit_point(ray, self.scene.shadow_bias()) {
+                Some(info) => (
+                    vec3(1.0 - info.bary.x - info.bary.y, info.bary.x, info.bary.y),
+                    true,
+                ),
+                None => (self.background_color, false),
+            },
+            ShadingMode::BoundingBoxes => {
+                if self.hits_bounding_box_edge(ray) {
+                    // the outline covers the background too
+                    (vec3(1.0, 1.0, 0.0), true)
                 } else {
-                    match self.lighting_model {
-                        LightingModel::Cosine => {
-                            let mut cos = -ray.direction().dot(self.camera.look_dir()).min(0.0);
-                            // add a head shadow directly above
-                            if cos.acos().to_degrees() < 10.0 {
-                                cos = 0.0;
+                    return self.trace_with_stats(ray, self.max_bounces, rng);
+                }
+            }
+        };
+        let stats = TraceStats {
+            primary_hit: hit,
+            ..TraceStats::default()
+        };
+        (color, stats)
+    }
+
+    // whether the ray enters a mesh's bounding box near one of its edges
+    fn hits_bounding_box_edge(&self, ray: &Ray) -> bool {
+        self.scene.meshes().iter().any(|mesh| {
+            let bounding_box = mesh.bounding_box();
+            let Some(hit) = bounding_box.hit_point(ray, 0.0) else {
+                return false;
+            };
+            // the box is behind the camera, or the camera is inside it
+            if hit.ray_distance <= 0.0 {
+                return false;
+            }
+            let (min, max) = (bounding_box.min(), bounding_box.max());
+            let line_width = 0.01 * (max - min).max_element();
+            let distance_to_faces = (hit.position - min).abs().min((hit.position - max).abs());
+            // an edge is where two of the box's faces meet
+            distance_to_faces
+                .cmplt(Vec3::splat(line_width))
+                .bitmask()
+                .count_ones()
+                >= 2
+        })
+    }
+
+    pub fn trace(&self, ray: &Ray, max_bounces: usize, rng: &mut impl Rng) -> Vec3 {
+        self.trace_with_stats(ray, max_bounces, rng).0
+    }
+
+    pub fn trace_with_stats(
+        &self,
+        ray: &Ray,
+        max_bounces: usize,
+        rng: &mut impl Rng,
+    ) -> (Vec3, TraceStats) {
+        self.trace_path(ray, None, max_bounces, rng)
+    }
+
+    // `primary_hit` is the already traced first hit of `ray` when it came from a packet,
+    // None to trace it here
+    fn trace_path(
+        &self,
+        ray: &Ray,
+        mut primary_hit: Option<Option<HitInfo>>,
+        max_bounces: usize,
+        rng: &mut impl Rng,
+    ) -> (Vec3, TraceStats) {
+        #[cfg(puffin)]
+        puffin::profile_function!();
+        let mut stats = TraceStats::default();
+        // the ray tree is walked with an explicit stack, each branch carrying the
+        // fraction of light it contributes to the pixel
+        let mut color = Vec3::ZERO;
+        let mut stack = vec![PathSegment {
+            ray: *ray,
+            throughput: Vec3::ONE,
+            bounces_left: max_bounces,
+            // the camera is assumed to sit in the exterior medium
+            media: MediumStack::default(),
+        }];
+
+        while let Some(PathSegment {
+            ray,
+            throughput,
+            bounces_left,
+            media,
+        }) = stack.pop()
+        {
+            stats.rays += 1;
+            let min_distance = if bounces_left == max_bounces {
+                self.primary_min_distance()
+            } else {
+                self.scene.shadow_bias()
+            };
+            // the primary segment is always popped first
+            let hit = match primary_hit.take() {
+                Some(hit) => hit,
+                None => self
+                    .scene
+                    .hit_point_within(&ray, min_distance, self.clip_far),
+            };
+            if stats.rays == 1 {
+                stats.primary_hit = hit.is_some();
+            }
+            match hit {
+                Some(info) => {
+                    if bounces_left == 0 {
+                        continue;
+                    }
+                    // Beer's law, once per interior segment: the path between two consecutive
+                    // interface crossings is attenuated by exp(-absorption * length) in the
+                    // medium it lies in, the innermost one. every branch leaving this hit,
+                    // reflected or transmitted, carried light across all of it. the GPU
+                    // shaders only handle a single stone and apply the same rule when folding
+                    // their bounce lists
+                    let throughput = match media.innermost() {
+                        Some(medium) => {
+                            // a segment that reaches an inclusion before the surface stops
+                            // there and carries on in a random direction
+                            let scatter_distance = medium.scatter_distance(rng);
+                            let distance = info.ray_distance.min(scatter_distance);
+                            if self.uv_light {
+                                color += throughput * medium.glow(distance);
+                            }
+                            let throughput = throughput * (-medium.absorption * distance).exp();
+                            if scatter_distance < info.ray_distance {
+                                let position = ray.origin() + ray.direction() * distance;
+                                PathSegment {
+                                    ray: Ray::new(position, uniform_sample_sphere(rng)),
+                                    throughput: throughput * medium.inclusion_color,
+                                    bounces_left: bounces_left - 1,
+                                    media,
+                                }
+                                .push_to(
+                                    &mut stack,
+                                    max_bounces - bounces_left + 1,
+                                    self.rr_start_bounce,
+                                    rng,
+                                );
+                                continue;
                             }
-                            Vec3::splat(self.light_intensity) * cos
+                            throughput
                         }
-                        LightingModel::Isometric => {
-                            if ray.direction().dot(-self.camera.look_dir()) >= 0.0 {
-                                Vec3::splat(self.light_intensity)
+                        None => throughput,
+                    };
+                    match info.material {
+                        Material::Refractive {
+                            color: absorption,
+                            refractive_index,
+                            ..
+                        }
+                        | Material::ThinFilm {
+                            color: absorption,
+                            refractive_index,
+                            ..
+                        } => {
+                            let (inclusion_density, inclusion_color) = info.material.inclusions();
+                            let medium = Medium {
+                                refractive_index,
+                                absorption,
+                                fluorescence: info.material.fluorescence(),
+                                inclusion_density,
+                                inclusion_color,
+                            };
+                            // the refracted branch crosses into the volume on the other side
+                            let (normal, eta_i, eta_t, refracted_media) = if info.front_face {
+                                let inside = media.entered(medium);
+                                (
+                                    info.normal,
+                                    media.refractive_index(self.exterior_ri),
+                                    refractive_index,
+                                    inside,
+                                )
                             } else {
-                                Vec3::splat(0.0)
+                                let outside = media.left(medium);
+                                (
+                                    -info.normal,
+                                    refractive_index,
+                                    outside.refractive_index(self.exterior_ri),
+                                    outside,
+                                )
+                            };
+                            // per channel, thin film interference tints the reflection
+                            let reflection_ratio = match info.material {
+                                Material::ThinFilm {
+                                    thickness_nm,
+                                    film_ri,
+                                    ..
+                                } if info.front_face => thin_film_reflectance(
+                                    ray.direction().dot(normal).abs(),
+                                    eta_i,
+                                    film_ri,
+                                    eta_t,
+                                    thickness_nm,
+                                ),
+                                _ => Vec3::splat(fresnel(ray.direction(), normal, eta_i, eta_t)),
+                            };
+
+                            if reflection_ratio.min_element() >= 1.0 {
+                                stats.total_internal_reflections += 1;
                             }
+
+                            let bounce = max_bounces - bounces_left + 1;
+                            // refraction branch
+                            if reflection_ratio.min_element() < 1.0 {
+                                #[cfg(puffin)]
+                                puffin::profile_scope!("Refraction Ray");
+                                let ri_ratio = eta_i / eta_t;
+
+                                debug_assert!(
+                                    ray.direction().is_normalized() && normal.is_normalized()
+                                );
+                                let out_direction = refract(ray.direction(), normal, ri_ratio);
+                                let out_origin = info.position;
+
+                                PathSegment {
+                                    ray: Ray::new(out_origin, out_direction),
+                                    throughput: throughput * (1.0 - reflection_ratio),
+                                    bounces_left: bounces_left - 1,
+                                    media: refracted_media,
+                                }
+                                .push_to(
+                                    &mut stack,
+                                    bounce,
+                                    self.rr_start_bounce,
+                                    rng,
+                                );
+                            }
+
+                            // reflection branch
+                            {
+                                #[cfg(puffin)]
+                                puffin::profile_scope!("Reflection ray");
+                                let out_direction = reflect(ray.direction(), normal);
+                                let out_origin = info.position;
+
+                                PathSegment {
+                                    ray: Ray::new(out_origin, out_direction),
+                                    throughput: throughput * reflection_ratio,
+                                    bounces_left: bounces_left - 1,
+                                    media,
+                                }
+                                .push_to(
+                                    &mut stack,
+                                    bounce,
+                                    self.rr_start_bounce,
+                                    rng,
+                                );
+                            }
+                        }
+                        Material::Diffuse {
+                            color: albedo,
+                            roughness,
+                        } => {
+                            color +=
+                                throughput * self.shade_diffuse(albedo, roughness, &ray, &info, rng)
                         }
+                        Material::Textured {
+                            texture,
+                            color: flat_color,
+                        } => {
+                            let albedo = info
+                                .uv
+                                .zip(self.scene.texture(texture))
+                                .map_or(flat_color, |(uv, image)| sample_bilinear(image, uv));
+                            color += throughput * self.shade_diffuse(albedo, 0.0, &ray, &info, rng)
+                        }
+                        Material::Anisotropic {
+                            color: albedo,
+                            specular,
+                            tangent,
+                            roughness_along,
+                            roughness_across,
+                        } => {
+                            let lobe = WardLobe {
+                                specular,
+                                tangent,
+                                roughness_along,
+                                roughness_across,
+                            };
+                            color += throughput
+                                * (self.shade_diffuse(albedo, 0.0, &ray, &info, rng)
+                                    + self.shade_ward(&lobe, &ray, &info))
+                        }
+                        Material::Checker { .. } => {
+                            let albedo = info.material.color_at(info.position);
+                            color += throughput * self.shade_diffuse(albedo, 0.0, &ray, &info, rng)
+                        }
+                        Material::Light { color: emitted } => color += throughput * emitted,
                     }
                 }
+                None => {
+                    let miss_color = if bounces_left == max_bounces || self.refracted_background {
+                        self.background_color
+                    } else {
+                        self.escaped_light(ray.direction())
+                    };
+                    color += throughput * miss_color;
+                }
             }
         }
+
+        (color, stats)
+    }
+
+    // light arriving along a ray that leaves the scene
+    fn escaped_light(&self, direction: Vec3) -> Vec3 {
+        let sun = match self.sun {
+            Some(sun) if direction.dot(-sun.direction) >= sun.angular_radius.to_radians().cos() => {
+                sun.color
+            }
+            _ => Vec3::ZERO,
+        };
+        sun + self.ambient_light(direction)
+    }
+
+    // the lighting model's sky around the observer
+    fn ambient_light(&self, direction: Vec3) -> Vec3 {
+        let cos = direction.dot(-self.camera.look_dir());
+        // add a head shadow directly above
+        if cos.clamp(-1.0, 1.0).acos().to_degrees() < self.head_shadow_degrees {
+            return Vec3::ZERO;
+        }
+        match self.lighting_model {
+            LightingModel::Cosine => Vec3::splat(self.light_intensity) * cos.max(0.0),
+            LightingModel::Isometric => {
+                if cos >= 0.0 {
+                    Vec3::splat(self.light_intensity)
+                } else {
+                    Vec3::ZERO
+                }
+            }
+            LightingModel::Hemisphere { sharpness } => {
+                if cos >= 0.0 {
+                    Vec3::splat(self.light_intensity * cos.powf(sharpness))
+                } else {
+                    Vec3::ZERO
+                }
+            }
+        }
+    }
+
+    // Oren-Nayar shading (Lambertian at zero roughness), lit from the observer's side like the
+    // lighting models
+    fn shade_diffuse(
+        &self,
+        albedo: Vec3,
+        roughness: f32,
+        ray: &Ray,
+        info: &HitInfo,
+        rng: &mut impl Rng,
+    ) -> Vec3 {
+        let normal = if info.front_face {
+            info.normal
+        } else {
+            -info.normal
+        };
+        let to_viewer = -ray.direction();
+        let to_observer_light = -self.camera.look_dir();
+        let cos = normal.dot(to_observer_light).max(0.0);
+        let mut color = albedo
+            * self.light_intensity
+            * cos
+            * oren_nayar(normal, to_viewer, to_observer_light, roughness)
+            * self.unoccluded_fraction(info.position, normal, rng);
+        if self.direct_lighting {
+            color += self.sample_direct_lighting(
+                albedo,
+                roughness,
+                normal,
+                to_viewer,
+                info.position,
+                rng,
+            );
+        }
+        #[cfg(feature = "caustics")]
+        if let Some(photon_map) = &self.caustics {
+            color += photon_map.radiance(info.position, albedo);
+        }
+        color
+    }
+
+    // share of short hemisphere rays from `position` that escape nearby geometry, 1 without
+    // ambient occlusion. direct lighting casts its own shadows and isn't affected
+    fn unoccluded_fraction(&self, position: Vec3, normal: Vec3, rng: &mut impl Rng) -> f32 {
+        let Some(ao) = self.ao.filter(|ao| ao.samples > 0) else {
+            return 1.0;
+        };
+        let occluded = (0..ao.samples)
+            .filter(|_| {
+                let direction = cosine_sample_hemisphere(normal, rng);
+                self.scene
+                    .occluded(position, position + direction * ao.radius)
+            })
+            .count();
+        1.0 - occluded as f32 / ao.samples as f32
+    }
+
+    // specular part of Material::Anisotropic, lit by the observer's light and the sun. the
+    // diffuse base is shaded separately
+    fn shade_ward(&self, lobe: &WardLobe, ray: &Ray, info: &HitInfo) -> Vec3 {
+        let normal = if info.front_face {
+            info.normal
+        } else {
+            -info.normal
+        };
+        let to_viewer = -ray.direction();
+        let to_observer_light = -self.camera.look_dir();
+        let mut color = lobe.eval(normal, to_viewer, to_observer_light)
+            * self.light_intensity
+            * normal.dot(to_observer_light).max(0.0);
+        if let Some(sun) = self.sun {
+            let to_sun = -sun.direction;
+            // a small disc, treated as a directional light of the same total power
+            let solid_angle = PI * sun.angular_radius.to_radians().powi(2);
+            let shadow_ray = Ray::new(info.position, to_sun);
+            if !self.scene.hit_by(&shadow_ray, self.scene.shadow_bias()) {
+                color += lobe.eval(normal, to_viewer, to_sun)
+                    * sun.color
+                    * solid_angle
+                    * normal.dot(to_sun).max(0.0);
+            }
+        }
+        color
+    }
+
+    // light from emissive triangles reaching a diffuse surface, estimated with one light
+    // sample and one cosine-weighted sample combined by multiple importance sampling
+    fn sample_direct_lighting(
+        &self,
+        albedo: Vec3,
+        roughness: f32,
+        normal: Vec3,
+        to_viewer: Vec3,
+        position: Vec3,
+        rng: &mut impl Rng,
+    ) -> Vec3 {
+        let light_area = self.scene.light_area();
+        if light_area <= 0.0 {
+            return Vec3::ZERO;
+        }
+        let lambert = albedo * FRAC_1_PI;
+        let mut color = Vec3::ZERO;
+
+        // light sampling
+        if let Some(light) = self.scene.sample_light(rng) {
+            let to_light = light.position - position;
+            let distance = to_light.length();
+            let direction = to_light / distance;
+            let cos_surface = normal.dot(direction);
+            let cos_light = light.normal.dot(direction).abs();
+
+            if cos_surface > 0.0 && cos_light > 0.0 {
+                if !self.scene.occluded(position, light.position) {
+                    // convert the area density to a solid angle density
+                    let pdf_light = light.pdf_area * distance * distance / cos_light;
+                    let pdf_brdf = cos_surface * FRAC_1_PI;
+                    let brdf = lambert * oren_nayar(normal, to_viewer, direction, roughness);
+                    color += brdf * light.emission * cos_surface / pdf_light
+                        * power_heuristic(pdf_light, pdf_brdf);
+                }
+            }
+        }
+
+        // BRDF sampling
+        let direction = cosine_sample_hemisphere(normal, rng);
+        let cos_surface = normal.dot(direction);
+        let bounce_ray = Ray::new(position, direction);
+        if let Some(hit) = self.scene.hit_point(&bounce_ray, self.scene.shadow_bias()) {
+            if let Material::Light { color: emission } = hit.material {
+                let cos_light = hit.normal.dot(direction).abs();
+                if cos_surface > 0.0 && cos_light > 0.0 {
+                    let pdf_light = hit.ray_distance * hit.ray_distance / (cos_light * light_area);
+                    let pdf_brdf = cos_surface * FRAC_1_PI;
+                    // lambert * cos / pdf_brdf reduces to the albedo
+                    color += albedo
+                        * oren_nayar(normal, to_viewer, direction, roughness)
+                        * emission
+                        * power_heuristic(pdf_brdf, pdf_light);
+                }
+            }
+        }
+
+        color
     }
 }
 
@@ -298,12 +2074,182 @@ impl Default for RenderOptions {
     }
 }
 
-pub fn gamma_correct(color: Vec3) -> Vec3 {
-    color.powf(3.2f32.recip())
+// map ray counts to colors from blue (fewest) to red (most)
+pub fn bounce_heatmap(counts: &[u32]) -> Vec<Vec3> {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+    counts
+        .iter()
+        .map(|&c| vec3(0.0, 0.0, 1.0).lerp(vec3(1.0, 0.0, 0.0), c as f32 / max))
+        .collect()
+}
+
+// parameters of Material::Anisotropic's specular lobe
+struct WardLobe {
+    specular: Vec3,
+    tangent: Vec3,
+    roughness_along: f32,
+    roughness_across: f32,
+}
+
+impl WardLobe {
+    // Ward's anisotropic BRDF, all directions point away from the surface
+    fn eval(&self, normal: Vec3, to_viewer: Vec3, to_light: Vec3) -> Vec3 {
+        let (cos_i, cos_o) = (normal.dot(to_light), normal.dot(to_viewer));
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return Vec3::ZERO;
+        }
+        // the needle direction in the surface, or any direction when it runs along the normal
+        let tangent = (self.tangent - normal * normal.dot(self.tangent)).normalize_or_zero();
+        let tangent = if tangent == Vec3::ZERO {
+            normal.any_orthonormal_vector()
+        } else {
+            tangent
+        };
+        let bitangent = normal.cross(tangent);
+
+        let half = (to_viewer + to_light).normalize();
+        let (alpha_t, alpha_b) = (
+            self.roughness_along.max(1e-3),
+            self.roughness_across.max(1e-3),
+        );
+        let exponent = -((half.dot(tangent) / alpha_t).powi(2)
+            + (half.dot(bitangent) / alpha_b).powi(2))
+            / half.dot(normal).powi(2);
+        self.specular * exponent.exp() / (4.0 * PI * alpha_t * alpha_b * (cos_i * cos_o).sqrt())
+    }
+}
+
+// Oren-Nayar reflectance relative to Lambert for facet slope deviation `roughness` (radians),
+// all directions point away from the surface
+fn oren_nayar(normal: Vec3, to_viewer: Vec3, to_light: Vec3, roughness: f32) -> f32 {
+    if roughness <= 0.0 {
+        return 1.0;
+    }
+    let sigma2 = roughness * roughness;
+    let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+    let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+    let cos_i = normal.dot(to_light).clamp(0.0, 1.0);
+    let cos_o = normal.dot(to_viewer).clamp(0.0, 1.0);
+    let (sin_i, sin_o) = ((1.0 - cos_i * cos_i).sqrt(), (1.0 - cos_o * cos_o).sqrt());
+
+    // azimuthal difference between the two directions
+    let cos_phi = (to_light - normal * cos_i)
+        .normalize_or_zero()
+        .dot((to_viewer - normal * cos_o).normalize_or_zero())
+        .max(0.0);
+
+    // alpha is the steeper of the two angles from the normal, beta the shallower
+    let (sin_alpha, tan_beta) = if cos_i < cos_o {
+        (sin_i, sin_o / cos_o.max(1e-4))
+    } else {
+        (sin_o, sin_i / cos_i.max(1e-4))
+    };
+
+    a + b * cos_phi * sin_alpha * tan_beta
+}
+
+// primary hits given as (normal, distance), differing by more than `threshold` in normal
+// direction or relative depth, or where only one of them hit anything
+// rng for sample `index` of `pixel`, independent of which worker or packet traces it, so
+// renders are reproducible and a resumed render matches an uninterrupted one
+fn sample_rng(seed: u64, pixel: usize, index: usize) -> SmallRng {
+    // golden ratio multiple, so nearby seeds don't cancel against nearby pixels
+    let stream = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    SmallRng::seed_from_u64(stream ^ ((pixel as u64) << 32 | index as u64))
+}
+
+fn primary_hits_differ(a: Option<(Vec3, f32)>, b: Option<(Vec3, f32)>, threshold: f32) -> bool {
+    match (a, b) {
+        (Some((normal_a, depth_a)), Some((normal_b, depth_b))) => {
+            normal_a.dot(normal_b) < 1.0 - threshold
+                || (depth_a - depth_b).abs() > threshold * depth_a.min(depth_b)
+        }
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+// MIS weight for a sample drawn from the strategy with density `pdf`
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    a / (a + b)
+}
+
+// cosine-weighted direction in the hemisphere around `normal`
+pub(crate) fn cosine_sample_hemisphere(normal: Vec3, rng: &mut impl Rng) -> Vec3 {
+    let (tangent, bitangent) = normal.any_orthonormal_pair();
+    let phi = TAU * rng.gen::<f32>();
+    let r2 = rng.gen::<f32>();
+    let r = r2.sqrt();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - r2).sqrt()
+}
+
+// uniformly distributed unit vector, inclusions scatter the same in every direction
+fn uniform_sample_sphere(rng: &mut impl Rng) -> Vec3 {
+    let z = 1.0 - 2.0 * rng.gen::<f32>();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = TAU * rng.gen::<f32>();
+    vec3(r * phi.cos(), r * phi.sin(), z)
+}
+
+// zero any NaN/infinite channels so one bad sample doesn't poison the whole pixel
+fn finite_sample(sample: Vec3) -> Vec3 {
+    if sample.is_finite() {
+        return sample;
+    }
+    Vec3::from_array(
+        sample
+            .to_array()
+            .map(|c| if c.is_finite() { c } else { 0.0 }),
+    )
+}
+
+// blocks while `capacity` messages are queued, but gives up once the render is aborted or
+// the receiver is gone, returning whether the message was sent
+fn send_unless_aborted(
+    tx: &Sender<RenderMsg>,
+    message: RenderMsg,
+    abort_signal: &AbortSignal,
+    capacity: usize,
+) -> bool {
+    let state = &abort_signal.0;
+    {
+        let mut queued = state.queued.lock().unwrap();
+        while *queued >= capacity.max(1) {
+            if abort_signal.is_aborted() || state.receiver_dropped.load(Ordering::Relaxed) {
+                return false;
+            }
+            queued = state.dequeued.wait(queued).unwrap();
+        }
+        *queued += 1;
+    }
+    tx.send(message).is_ok()
+}
+
+// encode linear color for display with a pure power curve
+pub fn gamma_correct(color: Vec3, gamma: f32) -> Vec3 {
+    color.powf(gamma.recip())
+}
+
+// mirror `direction` about the surface with the given normal
+pub fn reflect(direction: Vec3, normal: Vec3) -> Vec3 {
+    (direction - 2.0 * direction.dot(normal) * normal).normalize()
+}
+
+// bend `direction` through an interface, `normal` faces the incoming ray and
+// `ri_ratio` is the incoming over the outgoing refractive index
+pub fn refract(direction: Vec3, normal: Vec3, ri_ratio: f32) -> Vec3 {
+    let cos_1 = -direction.dot(normal);
+
+    let out_perp = ri_ratio * (direction + cos_1 * normal);
+    let out_parallel = normal * -(1.0 - out_perp.length_squared().min(1.0)).sqrt();
+
+    out_perp + out_parallel
 }
 
 // calculate the proportion of color that should come from reflection vs refraction
-fn fresnel(incoming: Vec3, normal: Vec3, eta_i: f32, eta_t: f32) -> f32 {
+pub fn fresnel(incoming: Vec3, normal: Vec3, eta_i: f32, eta_t: f32) -> f32 {
     #[cfg(puffin)]
     puffin::profile_function!();
     let cos_i = incoming.dot(normal);
@@ -322,6 +2268,52 @@ fn fresnel(incoming: Vec3, normal: Vec3, eta_i: f32, eta_t: f32) -> f32 {
     }
 }
 
+// RGB reflectance of a thin film coating between two media, from the Airy interference
+// formula averaged over polarisations and integrated across the visible spectrum
+fn thin_film_reflectance(
+    cos_i: f32,
+    eta_i: f32,
+    eta_film: f32,
+    eta_t: f32,
+    thickness_nm: f32,
+) -> Vec3 {
+    const SAMPLES: usize = 16;
+
+    let sin2_i = 1.0 - cos_i * cos_i;
+    let sin2_film = (eta_i / eta_film).powi(2) * sin2_i;
+    let sin2_t = (eta_i / eta_t).powi(2) * sin2_i;
+    if sin2_film >= 1.0 || sin2_t >= 1.0 {
+        // total internal reflection
+        return Vec3::ONE;
+    }
+    let cos_film = (1.0 - sin2_film).sqrt();
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    // Fresnel amplitude coefficients at the top and bottom of the film
+    let r12_s = (eta_i * cos_i - eta_film * cos_film) / (eta_i * cos_i + eta_film * cos_film);
+    let r12_p = (eta_film * cos_i - eta_i * cos_film) / (eta_film * cos_i + eta_i * cos_film);
+    let r23_s = (eta_film * cos_film - eta_t * cos_t) / (eta_film * cos_film + eta_t * cos_t);
+    let r23_p = (eta_t * cos_film - eta_film * cos_t) / (eta_t * cos_film + eta_film * cos_t);
+
+    let mut reflected = SpectralAccumulator::new();
+    let mut white = SpectralAccumulator::new();
+    for i in 0..SAMPLES {
+        let nm =
+            WAVELENGTH_MIN + (i as f32 + 0.5) / SAMPLES as f32 * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+        // phase difference between the light reflected off each side of the film
+        let phase = 4.0 * PI * eta_film * thickness_nm * cos_film / nm;
+        let airy = |r12: f32, r23: f32| {
+            let interference = 2.0 * r12 * r23 * phase.cos();
+            (r12 * r12 + r23 * r23 + interference) / (1.0 + r12 * r12 * r23 * r23 + interference)
+        };
+        reflected.add(nm, (airy(r12_s, r23_s) + airy(r12_p, r23_p)) / 2.0);
+        white.add(nm, 1.0);
+    }
+
+    // normalise so a flat reflectance spectrum gives the same value in every channel
+    (reflected.to_srgb_linear() / white.to_srgb_linear()).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
 pub struct GpuRenderInfo {
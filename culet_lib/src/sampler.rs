@@ -0,0 +1,72 @@
+use glam::{vec2, Vec2};
+use rand::Rng;
+
+// where each of a pixel's samples lands within the pixel
+pub trait Sampler {
+    // offset from the pixel center in [-0.5, 0.5) on both axes for sample `index` of `count`,
+    // `pixel` identifies the pixel for samplers that decorrelate neighbours
+    fn pixel_offset(&self, pixel: usize, index: usize, count: usize, rng: &mut impl Rng) -> Vec2;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SamplerKind {
+    // independent uniform jitter, the first sample stays at the pixel center
+    Uniform,
+    // one jittered sample per cell of a grid with exactly `count` cells, as close to square as
+    // `count` factors. prime counts end up as a single row of cells
+    Stratified,
+    // R2 low-discrepancy sequence started at a hashed per-pixel offset, spreading samples
+    // evenly within a pixel and decorrelating neighbouring pixels
+    BlueNoise,
+}
+
+impl Sampler for SamplerKind {
+    fn pixel_offset(&self, pixel: usize, index: usize, count: usize, rng: &mut impl Rng) -> Vec2 {
+        match self {
+            Self::Uniform => {
+                if index == 0 {
+                    Vec2::ZERO
+                } else {
+                    vec2(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5))
+                }
+            }
+            Self::Stratified => {
+                let (columns, rows) = stratified_grid(count);
+                let cell = index % (columns * rows);
+                let (cell_x, cell_y) = ((cell % columns) as f32, (cell / columns) as f32);
+                vec2(
+                    (cell_x + rng.gen::<f32>()) / columns as f32,
+                    (cell_y + rng.gen::<f32>()) / rows as f32,
+                ) - 0.5
+            }
+            Self::BlueNoise => {
+                // plastic number constants for the R2 sequence
+                const ALPHA: Vec2 = Vec2::new(0.754_877_7, 0.569_840_3);
+                let start = (hash(pixel as u32) % 4096) as f32;
+                (ALPHA * (start + index as f32)).fract() - 0.5
+            }
+        }
+    }
+}
+
+// columns and rows of the stratified grid for `count` samples, the largest factor of `count`
+// no bigger than its square root gives the rows
+pub fn stratified_grid(count: usize) -> (usize, usize) {
+    let count = count.max(1);
+    let rows = (1..=count)
+        .take_while(|rows| rows * rows <= count)
+        .filter(|rows| count % rows == 0)
+        .last()
+        .unwrap_or(1);
+    (count / rows, rows)
+}
+
+// integer hash for per-pixel shifts (lowbias32)
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
@@ -1,48 +1,461 @@
-use crate::{hittable::Hittable, mesh::Mesh, ray::Ray};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use glam::Vec3;
+use image::RgbImage;
+use rand::Rng;
+
+use crate::{
+    cuboid::Cuboid,
+    hittable::{HitInfo, Hittable},
+    material::Material,
+    mesh::{BoundingBox, Mesh},
+    ray::Ray,
+    sphere::Sphere,
+};
+
+// most rays `Scene::hit_packet_within` traces together, one per bit of the active mask
+pub const MAX_PACKET_RAYS: usize = 64;
+
+// minimum distance along a ray before a hit counts, to avoid re-hitting the surface it left
+pub const DEFAULT_SHADOW_BIAS: f32 = 1e-5;
 
 #[derive(Clone, Debug)]
 pub struct Scene {
     meshes: Vec<Mesh>,
+    // primitives are tested one by one after the meshes, there are only ever a few
+    spheres: Vec<Sphere>,
+    cuboids: Vec<Cuboid>,
     shadow_bias: f32,
+    // (mesh, triangle) indices of every emissive triangle, with their cumulative areas
+    lights: Vec<(usize, usize)>,
+    light_cdf: Vec<f32>,
+    // images referenced by index from `Material::Textured`
+    textures: Vec<Arc<RgbImage>>,
+    // top-level BVH over the meshes' bounding boxes, None for an empty scene
+    bvh: Option<MeshBvhNode>,
+}
+
+#[derive(Clone, Debug)]
+enum MeshBvhNode {
+    // index into `Scene::meshes`, the mesh tests its own bounding box
+    Leaf(usize),
+    Branch {
+        bounding_box: BoundingBox,
+        children: Box<[MeshBvhNode; 2]>,
+    },
+}
+
+impl MeshBvhNode {
+    fn build(meshes: &[Mesh], mut indices: Vec<usize>) -> (Self, BoundingBox) {
+        if indices.len() == 1 {
+            let index = indices[0];
+            return (Self::Leaf(index), meshes[index].bounding_box().clone());
+        }
+
+        // split at the median mesh center along the widest axis of the centers
+        let (min, max) = indices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &i| {
+                let center = meshes[i].bounding_box().center();
+                (min.min(center), max.max(center))
+            },
+        );
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        indices.sort_by(|&a, &b| {
+            let a = meshes[a].bounding_box().center()[axis];
+            let b = meshes[b].bounding_box().center()[axis];
+            a.total_cmp(&b)
+        });
+        let right = indices.split_off(indices.len() / 2);
+
+        let (left, left_box) = Self::build(meshes, indices);
+        let (right, right_box) = Self::build(meshes, right);
+        let bounding_box = left_box.union(&right_box);
+        (
+            Self::Branch {
+                bounding_box: bounding_box.clone(),
+                children: Box::new([left, right]),
+            },
+            bounding_box,
+        )
+    }
+
+    fn hit_point(
+        &self,
+        meshes: &[Mesh],
+        ray: &Ray,
+        min_distance: f32,
+        max_distance: f32,
+        closest: &mut Option<HitInfo>,
+    ) {
+        match self {
+            Self::Leaf(index) => {
+                if let Some(info) = meshes[*index].hit_point(ray, min_distance) {
+                    keep_closer(closest, info, max_distance);
+                }
+            }
+            Self::Branch {
+                bounding_box,
+                children,
+            } => {
+                let Some(entry) = bounding_box.hit_point(ray, min_distance) else {
+                    return;
+                };
+                // everything in this box is further away than the closest hit so far
+                let bound = closest.map_or(max_distance, |c| c.ray_distance);
+                if entry.ray_distance > bound {
+                    return;
+                }
+                for child in children.iter() {
+                    child.hit_point(meshes, ray, min_distance, max_distance, closest);
+                }
+            }
+        }
+    }
+
+    // whether anything lies between `min_distance` and `max_distance`, without finding out
+    // what is closest
+    fn hit_any(&self, meshes: &[Mesh], ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        match self {
+            Self::Leaf(index) => meshes[*index].hit_any(ray, min_distance, max_distance),
+            Self::Branch {
+                bounding_box,
+                children,
+            } => {
+                bounding_box
+                    .hit_point(ray, min_distance)
+                    .is_some_and(|entry| entry.ray_distance <= max_distance)
+                    && children
+                        .iter()
+                        .any(|child| child.hit_any(meshes, ray, min_distance, max_distance))
+            }
+        }
+    }
+
+    // like `hit_point`, but for every ray of a packet selected by the `active` bitmask. a
+    // node is skipped once none of the rays still reach its box before their closest hit
+    fn hit_packet(
+        &self,
+        meshes: &[Mesh],
+        rays: &[Ray],
+        active: u64,
+        min_distance: f32,
+        max_distance: f32,
+        closest: &mut [Option<HitInfo>],
+    ) {
+        match self {
+            Self::Leaf(index) => {
+                meshes[*index].hit_packet(rays, active, min_distance, max_distance, closest)
+            }
+            Self::Branch {
+                bounding_box,
+                children,
+            } => {
+                let mut entering = 0;
+                let mut remaining = active;
+                while remaining != 0 {
+                    let i = remaining.trailing_zeros() as usize;
+                    remaining &= remaining - 1;
+                    let bound = closest[i].map_or(max_distance, |c| c.ray_distance);
+                    if bounding_box
+                        .hit_point(&rays[i], min_distance)
+                        .is_some_and(|entry| entry.ray_distance <= bound)
+                    {
+                        entering |= 1 << i;
+                    }
+                }
+                if entering == 0 {
+                    return;
+                }
+                for child in children.iter() {
+                    child.hit_packet(meshes, rays, entering, min_distance, max_distance, closest);
+                }
+            }
+        }
+    }
+}
+
+// replace `closest` with `info` if it is nearer, or the first hit within `max_distance`
+fn keep_closer(closest: &mut Option<HitInfo>, info: HitInfo, max_distance: f32) {
+    let closer = match closest {
+        Some(c) => info.ray_distance < c.ray_distance,
+        None => info.ray_distance <= max_distance,
+    };
+    if closer {
+        *closest = Some(info);
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub emission: Vec3,
+    // probability density of this sample with respect to light surface area
+    pub pdf_area: f32,
 }
 
 impl Hittable for Scene {
-    fn hit_point(&self, ray: &Ray, min_distance: f32) -> Option<crate::hittable::HitInfo> {
+    fn hit_point(&self, ray: &Ray, min_distance: f32) -> Option<HitInfo> {
+        self.hit_point_within(ray, min_distance, f32::INFINITY)
+    }
+    fn hit_point_within(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitInfo> {
         #[cfg(puffin)]
         puffin::profile_function!();
-        let mut closest_hit_distance = f32::INFINITY;
+        if ray.is_degenerate() {
+            return None;
+        }
         let mut closest_hit_info = None;
-        for mesh in self.meshes.iter() {
-            if let Some(info) = mesh.hit_point(ray, min_distance) {
-                if info.ray_distance < closest_hit_distance {
-                    closest_hit_distance = info.ray_distance;
-                    closest_hit_info = Some(info);
-                }
+        if let Some(bvh) = &self.bvh {
+            bvh.hit_point(
+                &self.meshes,
+                ray,
+                min_distance,
+                max_distance,
+                &mut closest_hit_info,
+            );
+        }
+        for primitive in self.primitives() {
+            if let Some(info) = primitive.hit_point(ray, min_distance) {
+                keep_closer(&mut closest_hit_info, info, max_distance);
             }
         }
         closest_hit_info
     }
+    // shadow rays only need to know something is in the way
+    fn hit_by(&self, ray: &Ray, min_distance: f32) -> bool {
+        self.hit_any(ray, min_distance, f32::INFINITY)
+    }
 }
 impl Scene {
     pub fn new(meshes: Vec<Mesh>) -> Self {
-        Self {
+        let mut scene = Self {
             meshes,
-            shadow_bias: 1e-6,
-        }
+            spheres: vec![],
+            cuboids: vec![],
+            shadow_bias: DEFAULT_SHADOW_BIAS,
+            lights: vec![],
+            light_cdf: vec![],
+            textures: vec![],
+            bvh: None,
+        };
+        scene.rebuild();
+        scene
     }
     pub fn empty() -> Self {
         Self {
             meshes: vec![],
-            shadow_bias: 1e-6,
+            spheres: vec![],
+            cuboids: vec![],
+            shadow_bias: DEFAULT_SHADOW_BIAS,
+            lights: vec![],
+            light_cdf: vec![],
+            textures: vec![],
+            bvh: None,
         }
     }
+    // `hit_point_within` for each of up to MAX_PACKET_RAYS rays, in the same order. the
+    // BVH is descended once for the whole packet, which pays off when the rays are coherent,
+    // like primary rays through neighbouring pixels
+    pub fn hit_packet_within(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitInfo>> {
+        #[cfg(puffin)]
+        puffin::profile_function!();
+        assert!(
+            rays.len() <= MAX_PACKET_RAYS,
+            "packets hold at most {MAX_PACKET_RAYS} rays"
+        );
+        let mut closest = vec![None; rays.len()];
+        let active = rays
+            .iter()
+            .enumerate()
+            .filter(|(_, ray)| !ray.is_degenerate())
+            .fold(0u64, |mask, (i, _)| mask | 1 << i);
+        if let Some(bvh) = &self.bvh {
+            bvh.hit_packet(
+                &self.meshes,
+                rays,
+                active,
+                min_distance,
+                max_distance,
+                &mut closest,
+            );
+        }
+        for primitive in self.primitives() {
+            for (ray, closest) in rays.iter().zip(closest.iter_mut()) {
+                if let Some(info) = primitive.hit_point(ray, min_distance) {
+                    keep_closer(closest, info, max_distance);
+                }
+            }
+        }
+        closest
+    }
+    // whether the segment between two points is blocked, e.g. a shadow ray to a point on a
+    // light. both ends are pulled in by the shadow bias so the surfaces they lie on don't
+    // count, and traversal stops at the first occluder rather than the closest
+    pub fn occluded(&self, from: Vec3, to: Vec3) -> bool {
+        let offset = to - from;
+        let ray = Ray::new(from, offset);
+        self.hit_any(&ray, self.shadow_bias, offset.length() - self.shadow_bias)
+    }
+    fn hit_any(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        if ray.is_degenerate() {
+            return false;
+        }
+        self.bvh
+            .as_ref()
+            .is_some_and(|bvh| bvh.hit_any(&self.meshes, ray, min_distance, max_distance))
+            || self.primitives().any(|primitive| {
+                primitive
+                    .hit_point(ray, min_distance)
+                    .is_some_and(|info| info.ray_distance <= max_distance)
+            })
+    }
     pub fn shadow_bias(&self) -> f32 {
         self.shadow_bias
     }
-    pub fn meshes(&self) -> std::slice::Iter<'_, Mesh> {
-        self.meshes.iter()
+    pub fn set_shadow_bias(&mut self, shadow_bias: f32) {
+        self.shadow_bias = shadow_bias;
+    }
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+    // mutable access to the meshes, the BVH and light list are rebuilt when the guard drops
+    pub fn meshes_mut(&mut self) -> MeshesMut<'_> {
+        MeshesMut { scene: self }
+    }
+    // edit the meshes in place. the BVH and light list are rebuilt afterwards, since repairs
+    // and material changes move or relight triangles
+    pub fn modify_meshes<R>(&mut self, modify: impl FnOnce(&mut [Mesh]) -> R) -> R {
+        let result = modify(&mut self.meshes);
+        self.rebuild();
+        result
+    }
+    pub fn push_mesh(&mut self, mesh: Mesh) {
+        self.meshes.push(mesh);
+        self.rebuild();
+    }
+    pub fn spheres(&self) -> &[Sphere] {
+        &self.spheres
+    }
+    // primitives don't take part in light sampling, an emissive one is only found by rays
+    // that happen to hit it
+    pub fn push_sphere(&mut self, sphere: Sphere) {
+        self.spheres.push(sphere);
+    }
+    pub fn cuboids(&self) -> &[Cuboid] {
+        &self.cuboids
+    }
+    pub fn push_cuboid(&mut self, cuboid: Cuboid) {
+        self.cuboids.push(cuboid);
+    }
+    fn primitives(&self) -> impl Iterator<Item = &dyn Hittable> {
+        let spheres = self.spheres.iter().map(|s| s as &dyn Hittable);
+        let cuboids = self.cuboids.iter().map(|c| c as &dyn Hittable);
+        spheres.chain(cuboids)
+    }
+    // later meshes shift down by one, like Vec::remove
+    pub fn remove_mesh(&mut self, index: usize) -> Option<Mesh> {
+        if index >= self.meshes.len() {
+            return None;
+        }
+        let mesh = self.meshes.remove(index);
+        self.rebuild();
+        Some(mesh)
+    }
+
+    // returns the index to use in `Material::Textured`
+    pub fn add_texture(&mut self, image: Arc<RgbImage>) -> usize {
+        self.textures.push(image);
+        self.textures.len() - 1
+    }
+    pub fn texture(&self, index: usize) -> Option<&RgbImage> {
+        self.textures.get(index).map(|t| t.as_ref())
     }
-    pub fn meshes_mut(&mut self) -> std::slice::IterMut<'_, Mesh> {
-        self.meshes.iter_mut()
+
+    // refresh everything derived from the mesh list
+    fn rebuild(&mut self) {
+        self.bvh = (!self.meshes.is_empty())
+            .then(|| MeshBvhNode::build(&self.meshes, (0..self.meshes.len()).collect()).0);
+        self.collect_lights();
+    }
+
+    fn collect_lights(&mut self) {
+        self.lights.clear();
+        self.light_cdf.clear();
+        let mut total_area = 0.0;
+        for (m, mesh) in self.meshes.iter().enumerate() {
+            for (t, triangle) in mesh.triangle_slice().iter().enumerate() {
+                if let Material::Light { .. } = triangle.material() {
+                    total_area += triangle.area();
+                    self.lights.push((m, t));
+                    self.light_cdf.push(total_area);
+                }
+            }
+        }
+    }
+    pub fn light_area(&self) -> f32 {
+        self.light_cdf.last().copied().unwrap_or(0.0)
+    }
+    // pick a point uniformly over the total area of all emissive triangles
+    pub fn sample_light(&self, rng: &mut impl Rng) -> Option<LightSample> {
+        let total_area = self.light_area();
+        if total_area <= 0.0 {
+            return None;
+        }
+        let target = rng.gen::<f32>() * total_area;
+        let index = self
+            .light_cdf
+            .partition_point(|&area| area < target)
+            .min(self.lights.len() - 1);
+        let (m, t) = self.lights[index];
+        let triangle = &self.meshes[m].triangle_slice()[t];
+
+        Some(LightSample {
+            position: triangle.sample_point(rng.gen(), rng.gen()),
+            normal: triangle.normal(),
+            emission: triangle.material().color(),
+            pdf_area: total_area.recip(),
+        })
+    }
+}
+
+// returned by Scene::meshes_mut. derefs to the mesh slice and refreshes everything derived
+// from it once the edits are done
+pub struct MeshesMut<'a> {
+    scene: &'a mut Scene,
+}
+
+impl Deref for MeshesMut<'_> {
+    type Target = [Mesh];
+
+    fn deref(&self) -> &[Mesh] {
+        &self.scene.meshes
+    }
+}
+
+impl DerefMut for MeshesMut<'_> {
+    fn deref_mut(&mut self) -> &mut [Mesh] {
+        &mut self.scene.meshes
+    }
+}
+
+impl Drop for MeshesMut<'_> {
+    fn drop(&mut self) {
+        self.scene.rebuild();
     }
 }
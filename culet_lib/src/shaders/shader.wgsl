@@ -198,8 +198,9 @@ fn trace(pixel_ray: Ray, max_depth: i32, color_index: i32) -> vec3f {
     for (var i = max_depth - 1; i > 0; i--) {
         let refraction_color = refraction_colors[i] * (1.0 - reflection_info[i].reflection_ratio);
 
-        // reflection color gets attenuated wrt Beer's law and mixed with the reflection ratio
-        color = refraction_color + color * reflection_info[i].reflection_ratio * exp(-render_info.attenuation * reflection_info[i].ray_distance);
+        // light leaving at bounce i, and everything reflected back into it there, crossed the
+        // interior segment ending at bounce i, so both are attenuated by Beer's law over it
+        color = (refraction_color + color * reflection_info[i].reflection_ratio) * exp(-render_info.attenuation * reflection_info[i].ray_distance);
     }
 
     // blend first reflection and refraction
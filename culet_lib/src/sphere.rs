@@ -0,0 +1,70 @@
+use std::f32::consts::{FRAC_1_PI, PI};
+
+use glam::*;
+
+use crate::{
+    hittable::{HitInfo, Hittable},
+    material::Material,
+    ray::Ray,
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f32, material: Material) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit_point(&self, ray: &Ray, min_distance: f32) -> Option<HitInfo> {
+        if ray.is_degenerate() {
+            return None;
+        }
+        // |origin + t * direction - center|^2 = radius^2 with a unit direction, so the
+        // quadratic's leading coefficient is 1
+        let oc = ray.origin() - self.center;
+        let half_b = oc.dot(ray.direction());
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+
+        // the near intersection, or the far one when the ray starts inside
+        let mut t = -half_b - root;
+        if t <= min_distance {
+            t = -half_b + root;
+            if t <= min_distance {
+                return None;
+            }
+        }
+
+        let position = ray.origin() + t * ray.direction();
+        let normal = (position - self.center) / self.radius;
+        // longitude around +y from +x, latitude from the south pole
+        let uv = vec2(
+            0.5 + normal.z.atan2(normal.x) * 0.5 * FRAC_1_PI,
+            normal.y.clamp(-1.0, 1.0).asin() / PI + 0.5,
+        );
+        Some(HitInfo {
+            position,
+            normal,
+            ray_distance: t,
+            front_face: ray.direction().dot(normal) < 0.0,
+            material: self.material,
+            bary: Vec2::ZERO,
+            uv: Some(uv),
+        })
+    }
+}
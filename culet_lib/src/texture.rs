@@ -0,0 +1,30 @@
+use glam::{Vec2, Vec3};
+use image::RgbImage;
+
+use crate::color::srgb_to_linear;
+
+// bilinearly filtered texture lookup, wrapping UVs outside [0, 1). texels are sRGB encoded
+// and are decoded to linear before filtering
+pub fn sample_bilinear(image: &RgbImage, uv: Vec2) -> Vec3 {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Vec3::ZERO;
+    }
+
+    // texel centres sit at half-integer coordinates, v runs from the bottom of the image
+    let x = uv.x.rem_euclid(1.0) * width as f32 - 0.5;
+    let y = (1.0 - uv.y).rem_euclid(1.0) * height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as i64).rem_euclid(height as i64) as u32;
+        let [r, g, b] = image.get_pixel(x, y).0;
+        srgb_to_linear(Vec3::new(r as f32, g as f32, b as f32) / u8::MAX as f32)
+    };
+
+    let top = texel(x0, y0).lerp(texel(x0 + 1.0, y0), tx);
+    let bottom = texel(x0, y0 + 1.0).lerp(texel(x0 + 1.0, y0 + 1.0), tx);
+    top.lerp(bottom, ty)
+}
@@ -1,16 +1,70 @@
-use std::sync::{mpsc::channel, Arc};
+use std::{
+    fmt,
+    sync::{mpsc::channel, Arc, Mutex},
+    time::Duration,
+};
 
-use glam::vec3;
+use glam::{vec3, Vec3};
+use image::RgbaImage;
 use wgpu::{util::DeviceExt, Device, Queue};
 
 use crate::{
     camera::Camera,
+    color::linear_to_srgb,
     mesh::{GpuTriangle, Mesh},
     render::GpuRenderInfo,
 };
 
 pub const TEXTURE_SIZE: u32 = 1024;
 
+#[derive(Debug)]
+pub enum WgpuError {
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    BufferMap(wgpu::BufferAsyncError),
+    // the map callback was dropped without running, e.g. after device loss
+    MapCallbackDropped,
+}
+
+impl fmt::Display for WgpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter => write!(f, "no suitable GPU adapter found"),
+            Self::RequestDevice(e) => write!(f, "failed to create GPU device: {e}"),
+            Self::BufferMap(e) => write!(f, "failed to read back GPU output: {e}"),
+            Self::MapCallbackDropped => write!(f, "GPU output readback never completed"),
+        }
+    }
+}
+
+impl std::error::Error for WgpuError {}
+
+impl From<wgpu::RequestDeviceError> for WgpuError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for WgpuError {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        Self::BufferMap(e)
+    }
+}
+
+// which compiled form of the compute shader a WgpuHandle runs, for comparing the
+// hand-written WGSL against another compiler's output
+#[derive(Clone, Debug, Default)]
+pub enum ShaderBackend {
+    // shaders/shader.wgsl, built into the library
+    #[default]
+    Wgsl,
+    // a SPIR-V module, e.g. from rust-gpu, with the same bindings as shader.wgsl
+    SpirV {
+        module: Vec<u8>,
+        entry_point: String,
+    },
+}
+
 #[derive(Debug)]
 pub struct WgpuHandle {
     device: Arc<Device>,
@@ -23,10 +77,71 @@ pub struct WgpuHandle {
     camera_bind_group: wgpu::BindGroup,
     render_info_bind_group: wgpu::BindGroup,
     pipeline: wgpu::ComputePipeline,
+    timestamps: Option<GpuTimestamps>,
+    last_gpu_time: Mutex<Option<Duration>>,
+}
+
+// queries written at the start and end of the compute pass, only when the device supports them
+#[derive(Debug)]
+struct GpuTimestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+}
+
+impl GpuTimestamps {
+    fn new(device: &Device) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let size = 2 * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Compute timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp resolve buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp readback buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        })
+    }
 }
 
 impl WgpuHandle {
-    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+    // create a standalone device on the default instance, preferring a discrete GPU
+    pub fn with_default_device(backend: ShaderBackend) -> Result<Self, WgpuError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))
+        .ok_or(WgpuError::NoAdapter)?;
+        // GPU timing is optional, only ask for it where the adapter has it
+        let features = adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("culet device"),
+                features,
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))?;
+
+        Ok(Self::new(Arc::new(device), Arc::new(queue), backend))
+    }
+
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, backend: ShaderBackend) -> Self {
         // create a texture for the GPU to render to internally
         // store the RGB channels in separate textures next to each other
         let texture_desc = wgpu::TextureDescriptor {
@@ -87,7 +202,22 @@ impl WgpuHandle {
         };
         let render_info_buffer = device.create_buffer_init(&render_info_buffer_desc);
 
-        let shaders = device.create_shader_module(wgpu::include_wgsl!("shaders/shader.wgsl"));
+        let (shaders, entry_point) = match &backend {
+            ShaderBackend::Wgsl => (
+                device.create_shader_module(wgpu::include_wgsl!("shaders/shader.wgsl")),
+                "main",
+            ),
+            ShaderBackend::SpirV {
+                module,
+                entry_point,
+            } => (
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("SPIR-V compute shader"),
+                    source: wgpu::util::make_spirv(module),
+                }),
+                entry_point.as_str(),
+            ),
+        };
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -201,11 +331,10 @@ impl WgpuHandle {
             label: None,
             layout: Some(&pipeline_layout),
             module: &shaders,
-            entry_point: "main",
+            entry_point,
         });
 
         Self {
-            device,
             queue,
             texture,
             vertex_buffer: triangle_buffer,
@@ -215,10 +344,13 @@ impl WgpuHandle {
             camera_bind_group,
             render_info_bind_group,
             pipeline,
+            timestamps: GpuTimestamps::new(&device),
+            last_gpu_time: Mutex::new(None),
+            device,
         }
     }
 
-    pub fn render(&self, output_buffer: &mut [u8]) {
+    pub fn render(&self, output_buffer: &mut [u8]) -> Result<(), WgpuError> {
         let device = &self.device;
 
         let mut encoder =
@@ -226,7 +358,13 @@ impl WgpuHandle {
 
         let compute_pass_descriptor = wgpu::ComputePassDescriptor {
             label: Some("Compute Pass"),
-            timestamp_writes: None,
+            timestamp_writes: self.timestamps.as_ref().map(|timestamps| {
+                wgpu::ComputePassTimestampWrites {
+                    query_set: &timestamps.query_set,
+                    beginning_of_pass_write_index: Some(0),
+                    end_of_pass_write_index: Some(1),
+                }
+            }),
         };
 
         {
@@ -263,13 +401,62 @@ impl WgpuHandle {
             },
         );
 
+        if let Some(timestamps) = &self.timestamps {
+            encoder.resolve_query_set(&timestamps.query_set, 0..2, &timestamps.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &timestamps.resolve_buffer,
+                0,
+                &timestamps.readback_buffer,
+                0,
+                timestamps.readback_buffer.size(),
+            );
+        }
+
         self.queue.submit(Some(encoder.finish()));
 
+        let timestamp_receiver = self.timestamps.as_ref().map(|timestamps| {
+            let (sender, receiver) = channel();
+            timestamps
+                .readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |r| {
+                    let _ = sender.send(r);
+                });
+            receiver
+        });
+
         let (sender, receiver) = channel();
         let buffer_slice = self.output_buffer.slice(..);
-        buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+        buffer_slice.map_async(wgpu::MapMode::Read, move |r| {
+            let _ = sender.send(r);
+        });
         self.device.poll(wgpu::Maintain::Wait);
-        receiver.recv().unwrap().unwrap();
+
+        // timings are read before the output is checked, so the readback buffer is never left
+        // mapped for the next render. a failed map leaves nothing to unmap, that render just
+        // goes untimed
+        if let (Some(timestamps), Some(timestamp_receiver)) = (&self.timestamps, timestamp_receiver)
+        {
+            let elapsed = match timestamp_receiver.recv() {
+                Ok(Ok(())) => {
+                    let elapsed = {
+                        let view = timestamps.readback_buffer.slice(..).get_mapped_range();
+                        let ticks: &[u64] = bytemuck::cast_slice(&view);
+                        let nanos = ticks[1].wrapping_sub(ticks[0]) as f64
+                            * self.queue.get_timestamp_period() as f64;
+                        Duration::from_nanos(nanos as u64)
+                    };
+                    timestamps.readback_buffer.unmap();
+                    Some(elapsed)
+                }
+                _ => None,
+            };
+            *self.last_gpu_time.lock().unwrap() = elapsed;
+        }
+
+        receiver
+            .recv()
+            .map_err(|_| WgpuError::MapCallbackDropped)??;
         {
             let view = buffer_slice.get_mapped_range();
             output_buffer
@@ -279,10 +466,35 @@ impl WgpuHandle {
                     c[0] = view[4 * i];
                     c[1] = view[4 * (TEXTURE_SIZE * TEXTURE_SIZE) as usize + 4 * i + 1];
                     c[2] = view[2 * 4 * (TEXTURE_SIZE * TEXTURE_SIZE) as usize + 4 * i + 2];
+                    c[3] = view[4 * i + 3];
                 });
         }
 
         self.output_buffer.unmap();
+        Ok(())
+    }
+
+    // GPU time spent in the compute pass of the last render, None without timestamp query support
+    pub fn last_gpu_time(&self) -> Option<Duration> {
+        *self.last_gpu_time.lock().unwrap()
+    }
+
+    // the rendered frame, TEXTURE_SIZE square. render() already recombines the shader's three
+    // single channel bands into one picture, but leaves the color linear as the shader stored
+    // it in the rgba8unorm texture, so it's sRGB encoded here for saving or display
+    pub fn render_to_image(&self) -> Result<RgbaImage, WgpuError> {
+        let mut image = RgbaImage::new(TEXTURE_SIZE, TEXTURE_SIZE);
+        self.render(&mut image)?;
+        // only 256 possible inputs per channel
+        let encoded: Vec<u8> = (0..=255u8)
+            .map(|c| (linear_to_srgb(Vec3::splat(c as f32 / 255.0)).x * 255.0).round() as u8)
+            .collect();
+        for pixel in image.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                *channel = encoded[*channel as usize];
+            }
+        }
+        Ok(image)
     }
 
     pub fn set_camera(&mut self, new_camera: &Camera) {
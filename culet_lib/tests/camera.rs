@@ -0,0 +1,34 @@
+// framing of the pinhole camera
+use culet_lib::camera::Camera;
+use glam::Vec3;
+
+// width over height of the viewport rectangle
+fn viewport_shape(camera: &Camera) -> f32 {
+    let (_, width, height) = camera.viewport();
+    width.length() / height.length()
+}
+
+#[test]
+fn wider_aspect_gives_a_wider_viewport() {
+    let square = Camera::default().fov(60.0).aspect_ratio(1.0);
+    let wide = Camera::default().fov(60.0).aspect_ratio(2.0);
+
+    assert!((viewport_shape(&square) - 1.0).abs() < 1e-4);
+    assert!((viewport_shape(&wide) - 2.0).abs() < 1e-4);
+    // the horizontal fov is fixed, so the extra width comes out of the height
+    assert_eq!(square.horizontal_fov(), wide.horizontal_fov());
+    assert!((square.vertical_fov() - 60.0).abs() < 1e-3);
+    assert!(wide.vertical_fov() < square.vertical_fov());
+}
+
+// the target only sets the direction, it can be nearer than the focal length
+#[test]
+fn looks_at_a_point_inside_the_focal_length() {
+    let target = Vec3::new(0.3, 0.0, -0.4);
+    let camera = Camera::default().look_at(target);
+    assert_eq!(camera.focal_length, 1.0);
+    assert!((camera.look_dir() - target / 0.5).length() < 1e-6);
+    // the center ray still goes through the target
+    let center = camera.ray(0.5, 0.5);
+    assert!(center.direction().cross(target).length() < 1e-5);
+}
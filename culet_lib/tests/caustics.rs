@@ -0,0 +1,59 @@
+// photons focused through a stone onto the surface below it
+#![cfg(feature = "caustics")]
+mod common;
+
+use std::sync::Arc;
+
+use common::{box_mesh, gem, quad};
+use culet_lib::{material::Material, mesh::Mesh, render::RenderOptions, scene::Scene};
+use glam::{vec3, Vec3};
+
+// a small light above a glass cube resting on a large diffuse floor
+fn stone_on_a_table() -> Scene {
+    let floor = quad(
+        vec3(-10.0, 0.0, -10.0),
+        vec3(-10.0, 0.0, 10.0),
+        vec3(10.0, 0.0, 10.0),
+        vec3(10.0, 0.0, -10.0),
+    );
+    let light = quad(
+        vec3(-0.25, 3.0, -0.25),
+        vec3(-0.25, 3.0, 0.25),
+        vec3(0.25, 3.0, 0.25),
+        vec3(0.25, 3.0, -0.25),
+    );
+    Scene::new(vec![
+        Mesh::from_tris_with_material(
+            Vec3::ZERO,
+            floor,
+            Material::Diffuse {
+                color: Vec3::ONE,
+                roughness: 0.0,
+            },
+        ),
+        Mesh::from_tris_with_material(
+            Vec3::ZERO,
+            light,
+            Material::Light {
+                color: Vec3::splat(10.0),
+            },
+        ),
+        box_mesh(
+            vec3(-0.5, 0.5, -0.5),
+            vec3(0.5, 1.5, 0.5),
+            gem(1.5, Vec3::ZERO),
+        ),
+    ])
+}
+
+// getting through the cube takes at least two refractions and then the floor, so this
+// needs more than the camera's single bounce
+#[test]
+fn photons_through_a_stone_reach_the_floor() {
+    let options = RenderOptions::new()
+        .scene(Arc::new(stone_on_a_table()))
+        .max_bounces(1)
+        .caustics(2000, 0.1);
+    let photon_map = options.caustics.expect("caustics were requested");
+    assert!(!photon_map.is_empty());
+}
@@ -0,0 +1,102 @@
+// saving, loading and resuming render checkpoints
+mod common;
+
+use std::{io::ErrorKind, sync::Arc};
+
+use common::{box_mesh, gem, temp_file};
+use culet_lib::{checkpoint::Checkpoint, material::Material, render::RenderOptions, scene::Scene};
+use glam::{vec3, Vec3};
+
+fn checkpoint_header(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = b"CULETCKP".to_vec();
+    for value in [2, width, height] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn checkpoints_round_trip() {
+    let mut checkpoint = Checkpoint::new(3, 2);
+    checkpoint.record(4, vec3(0.25, 0.5, 1.0), 0.75, 4);
+    let path = temp_file("round-trip.ckp", &[]);
+    checkpoint.save(&path).unwrap();
+
+    let loaded = Checkpoint::load(&path).unwrap();
+    assert_eq!((loaded.width(), loaded.height()), (3, 2));
+    assert_eq!(loaded.samples(4), 4);
+    assert_eq!(loaded.hits(4), 3);
+    assert_eq!(loaded.color(4), vec3(0.25, 0.5, 1.0));
+    assert_eq!(loaded.samples(0), 0);
+}
+
+// a header claiming a huge image has to be rejected before anything that size is allocated
+#[test]
+fn oversized_header_is_rejected() {
+    let path = temp_file("oversized.ckp", &checkpoint_header(u32::MAX, u32::MAX));
+    let error = Checkpoint::load(&path).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn truncated_checkpoint_is_rejected() {
+    let path = temp_file("truncated.ckp", &[]);
+    Checkpoint::new(4, 4).save(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+
+    let error = Checkpoint::load(&path).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn unwritable_checkpoint_fails_the_render() {
+    let light = Material::Light { color: Vec3::ONE };
+    let options = RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![box_mesh(
+            vec3(-0.5, -0.5, -3.0),
+            vec3(0.5, 0.5, -2.0),
+            light,
+        )])))
+        .image_width(4)
+        .image_height(4)
+        .checkpoint(
+            std::env::temp_dir().join("culet-no-such-directory/render.ckp"),
+            std::time::Duration::from_secs(60),
+        );
+
+    assert!(options.render_checkpointed().is_err());
+    // the plain render doesn't write checkpoints, so it isn't affected
+    assert_eq!(options.render().len(), 16);
+}
+
+// a render stopped part way, with only some pixels and some of their samples done, and then
+// resumed must come out exactly as one that ran straight through
+#[test]
+fn resumed_render_matches_an_uninterrupted_one() {
+    let cube = box_mesh(
+        Vec3::splat(-0.5),
+        Vec3::splat(0.5),
+        gem(1.5, Vec3::splat(0.5)),
+    );
+    let options = RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![cube])))
+        .background_color(Vec3::ONE)
+        .image_width(12)
+        .image_height(8)
+        .max_bounces(8)
+        .samples_per_pixel(4)
+        .threads(3);
+    let straight = options.render();
+
+    let path = temp_file("resume.ckp", &[]);
+    options
+        .clone()
+        .samples_per_pixel(2)
+        .region(0, 0, 12, 5)
+        .checkpoint(&path, std::time::Duration::from_secs(60))
+        .render_checkpointed()
+        .unwrap();
+    let resumed = options.resume_from(&path).unwrap().render();
+    assert_eq!(resumed, straight);
+}
@@ -0,0 +1,81 @@
+use culet_lib::{
+    color::{
+        linear_to_srgb, srgb_to_linear, xyz_to_srgb_linear, SpectralAccumulator, WAVELENGTH_MAX,
+        WAVELENGTH_MIN,
+    },
+    render::{gamma_correct, RenderOptions},
+};
+use glam::Vec3;
+
+#[test]
+fn equal_energy_white_is_near_neutral() {
+    // a flat unit spectrum, sampled evenly across the visible range
+    let mut accumulator = SpectralAccumulator::new();
+    let steps = 4000;
+    for i in 0..steps {
+        let nm =
+            WAVELENGTH_MIN + (i as f32 + 0.5) / steps as f32 * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+        accumulator.add(nm, 1.0);
+    }
+
+    // illuminant E is the neutral point of XYZ
+    let xyz = accumulator.xyz();
+    for channel in xyz.to_array() {
+        assert!((channel - 1.0).abs() < 0.01, "xyz {xyz}");
+    }
+    // and sits slightly pink of the D65 white of sRGB, but nowhere near a saturated color
+    let rgb = xyz_to_srgb_linear(xyz);
+    assert!(rgb.min_element() > 0.0, "rgb {rgb}");
+    assert!(rgb.max_element() / rgb.min_element() < 1.4, "rgb {rgb}");
+    assert_eq!(rgb, accumulator.to_srgb_linear());
+}
+
+// encoding with a gamma undoes decoding with it
+#[test]
+fn gamma_round_trips_mid_gray() {
+    let decoded = Vec3::splat(0.5f32.powf(2.2));
+    let encoded = gamma_correct(decoded, 2.2);
+    assert!(
+        (encoded - Vec3::splat(0.5)).abs().max_element() < 1e-5,
+        "{encoded}"
+    );
+
+    let options = RenderOptions::new().gamma(2.2);
+    assert!(
+        (options.encode_color(decoded) - Vec3::splat(0.5))
+            .abs()
+            .max_element()
+            < 1e-5
+    );
+}
+
+// reference values of the piecewise sRGB encoding
+#[test]
+fn srgb_encoding_matches_reference_values() {
+    for (linear, encoded) in [(0.0, 0.0), (0.002, 0.025_84), (0.5, 0.735_357), (1.0, 1.0)] {
+        let actual = linear_to_srgb(Vec3::splat(linear));
+        assert!(
+            (actual - Vec3::splat(encoded)).abs().max_element() < 1e-5,
+            "{linear} encoded to {actual}, expected {encoded}"
+        );
+    }
+}
+
+// decoding and re-encoding every 8-bit level, and encoding and decoding linear values
+// spread over the range, stays within one level
+#[test]
+fn srgb_round_trips_within_one_level() {
+    for level in 0..=255 {
+        let encoded = Vec3::splat(level as f32 / 255.0);
+        let error = (linear_to_srgb(srgb_to_linear(encoded)) - encoded).abs();
+        assert!(error.max_element() < 1.0 / 255.0, "level {level}: {error}");
+    }
+    for i in 0..=1000 {
+        let linear = Vec3::splat(i as f32 / 1000.0);
+        let error = (srgb_to_linear(linear_to_srgb(linear)) - linear).abs();
+        assert!(
+            error.max_element() < 1.0 / 255.0,
+            "linear {linear}: {error}"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+// scene building shared by the integration tests, each test file uses a different part
+#![allow(dead_code)]
+
+use culet_lib::{
+    material::Material,
+    mesh::{Mesh, Triangle},
+};
+use std::path::PathBuf;
+
+use glam::Vec3;
+
+// the quad a, b, c, d as two triangles, facing the way the corners turn anticlockwise
+pub fn quad(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> [Triangle; 2] {
+    [Triangle::new(a, b, c), Triangle::new(a, c, d)]
+}
+
+// the 12 triangles of an axis-aligned box, all facing out
+pub fn box_triangles(min: Vec3, max: Vec3) -> Vec<Triangle> {
+    let mut triangles = Vec::new();
+    for i in 0..3 {
+        let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+        for positive in [false, true] {
+            let corner = |along_j: bool, along_k: bool| {
+                let mut p = min;
+                if positive {
+                    p[i] = max[i];
+                }
+                if along_j {
+                    p[j] = max[j];
+                }
+                if along_k {
+                    p[k] = max[k];
+                }
+                p
+            };
+            let (a, b, c, d) = (
+                corner(false, false),
+                corner(true, false),
+                corner(true, true),
+                corner(false, true),
+            );
+            // j x k is the +i axis
+            if positive {
+                triangles.extend(quad(a, b, c, d));
+            } else {
+                triangles.extend(quad(a, d, c, b));
+            }
+        }
+    }
+    triangles
+}
+
+pub fn box_mesh(min: Vec3, max: Vec3, material: Material) -> Mesh {
+    Mesh::from_tris_with_material(Vec3::ZERO, box_triangles(min, max), material)
+}
+
+// clean refractive stone, `absorption` per unit distance
+pub fn gem(refractive_index: f32, absorption: Vec3) -> Material {
+    Material::Refractive {
+        color: absorption,
+        refractive_index,
+        dispersion: 0.0,
+        fluorescence: Vec3::ZERO,
+        inclusion_density: 0.0,
+        inclusion_color: Vec3::ONE,
+    }
+}
+
+pub fn assert_close(actual: Vec3, expected: Vec3, tolerance: f32) {
+    assert!(
+        (actual - expected).abs().max_element() <= tolerance,
+        "{actual} is not within {tolerance} of {expected}"
+    );
+}
+
+// write `contents` to a file of its own under the system temp dir, named after the test
+pub fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("culet-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).expect("couldn't write the temp file");
+    path
+}
+
+// ASCII STL of the triangles, leaving the stored normals for the reader to recompute
+pub fn ascii_stl(triangles: &[Triangle]) -> String {
+    let mut stl = String::from("solid test\n");
+    for triangle in triangles {
+        stl += "facet normal 0 0 0\nouter loop\n";
+        for p in triangle.points() {
+            stl += &format!("vertex {} {} {}\n", p.x, p.y, p.z);
+        }
+        stl += "endloop\nendfacet\n";
+    }
+    stl + "endsolid test\n"
+}
+
+// a simple octagonal stone around +Z: a table of radius 0.5 at height 0.3, eight crown facets
+// down to a knife-edge girdle of radius 1 at z = 0, and eight pavilion facets meeting in a
+// pointed culet at z = -0.8. crown angle atan(0.3 / (0.5 cos 22.5°)) ≈ 33.0°, pavilion angle
+// atan(0.8 / cos 22.5°) ≈ 40.9°
+pub fn octagonal_stone() -> Vec<Triangle> {
+    let girdle: Vec<Vec3> = (0..8)
+        .map(|i| {
+            let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+            Vec3::new(angle.cos(), angle.sin(), 0.0)
+        })
+        .collect();
+    let table: Vec<Vec3> = girdle
+        .iter()
+        .map(|&g| 0.5 * g + Vec3::new(0.0, 0.0, 0.3))
+        .collect();
+    let culet = Vec3::new(0.0, 0.0, -0.8);
+
+    let mut triangles: Vec<Triangle> = (1..7)
+        .map(|i| Triangle::new(table[0], table[i], table[i + 1]))
+        .collect();
+    for i in 0..8 {
+        let j = (i + 1) % 8;
+        triangles.extend(quad(girdle[i], girdle[j], table[j], table[i]));
+        triangles.push(Triangle::new(girdle[j], girdle[i], culet));
+    }
+    triangles
+}
@@ -0,0 +1,191 @@
+// geometry of meshes built from triangles
+mod common;
+
+use common::{ascii_stl, box_triangles, octagonal_stone, quad, temp_file};
+use culet_lib::{
+    hittable::Hittable,
+    material::Material,
+    mesh::{Mesh, Triangle, MIN_BOX_THICKNESS},
+    ray::Ray,
+};
+use glam::{vec3, Vec3};
+
+// a 10x10 square in the z = 1000 plane, far from the origin so the padding can't hide in
+// the float spacing around zero
+fn flat_square() -> Mesh {
+    let [a, b, c, d] = [
+        vec3(0.0, 0.0, 1000.0),
+        vec3(10.0, 0.0, 1000.0),
+        vec3(10.0, 10.0, 1000.0),
+        vec3(0.0, 10.0, 1000.0),
+    ];
+    Mesh::from_tris_with_material(Vec3::ZERO, quad(a, b, c, d), Material::gem())
+}
+
+#[test]
+fn flat_mesh_has_a_thin_but_nonzero_bounding_box() {
+    let mesh = flat_square();
+    let extent = mesh.bounding_box().max() - mesh.bounding_box().min();
+    let volume = extent.x * extent.y * extent.z;
+    assert!(volume.is_finite() && volume > 0.0, "extent {extent}");
+    // padded relative to the square's size, not to its distance from the origin
+    assert!(
+        extent.z <= 2.0 * MIN_BOX_THICKNESS * 10.0,
+        "extent {extent}"
+    );
+
+    let ray = Ray::new(vec3(5.0, 5.0, 1010.0), Vec3::NEG_Z);
+    let hit = mesh.hit_point(&ray, 0.0).expect("ray misses the flat mesh");
+    assert!((hit.ray_distance - 10.0).abs() < 1e-3);
+}
+
+// one triangle in the z = 0 plane facing +Z, hit straight on from either side
+fn hits_from_each_side(triangle: Triangle) -> (bool, bool) {
+    let from = |z: f32| Ray::new(vec3(0.25, 0.25, z), vec3(0.0, 0.0, -z));
+    (
+        triangle.hit_point(&from(1.0), 0.0).is_some(),
+        triangle.hit_point(&from(-1.0), 0.0).is_some(),
+    )
+}
+
+fn facing_z() -> Triangle {
+    Triangle::new(Vec3::ZERO, Vec3::X, Vec3::Y)
+}
+
+#[test]
+fn single_sided_triangle_is_invisible_from_behind() {
+    let diffuse = Material::Diffuse {
+        color: Vec3::ONE,
+        roughness: 0.0,
+    };
+    let single = facing_z().with_material(diffuse).with_double_sided(false);
+    assert_eq!(hits_from_each_side(single), (true, false));
+
+    let double = facing_z().with_material(diffuse);
+    assert_eq!(hits_from_each_side(double), (true, true));
+}
+
+// light has to be able to leave a stone, so refractive and coated faces ignore the flag
+#[test]
+fn refractive_triangle_is_always_double_sided() {
+    let coated = Material::ThinFilm {
+        color: Vec3::ZERO,
+        refractive_index: 1.54,
+        thickness_nm: 300.0,
+        film_ri: 1.38,
+    };
+    for material in [Material::gem(), coated] {
+        let triangle = facing_z().with_material(material).with_double_sided(false);
+        assert_eq!(hits_from_each_side(triangle), (true, true), "{material:?}");
+    }
+}
+
+// a closed cube plus a zero-area sliver and a second copy of one of its triangles, as CAD
+// exports often have
+#[test]
+fn repair_drops_degenerate_and_duplicate_triangles() {
+    let mut triangles = box_triangles(Vec3::ZERO, Vec3::ONE);
+    triangles.push(Triangle::new(Vec3::ZERO, Vec3::X, Vec3::X * 2.0));
+    triangles.push(triangles[3]);
+    let path = temp_file("repair.stl", ascii_stl(&triangles).as_bytes());
+    let mut mesh = Mesh::load_from_stl(Vec3::ZERO, &path);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(mesh.triangle_count(), 14);
+    let report = mesh.validate();
+    assert_eq!((report.degenerate, report.duplicate), (1, 1));
+    assert!(mesh.triangle_slice().iter().all(|t| t.normal().is_finite()));
+
+    assert_eq!(mesh.repair(), 2);
+    assert_eq!(mesh.triangle_count(), 12);
+    let report = mesh.validate();
+    assert_eq!((report.degenerate, report.duplicate), (0, 0));
+    assert_eq!(report.non_manifold_edges, 0);
+    assert!(mesh
+        .triangle_slice()
+        .iter()
+        .all(|t| t.normal().is_normalized()));
+}
+
+#[test]
+fn cube_has_six_facets() {
+    let cube = Mesh::from_tris_with_material(
+        Vec3::ZERO,
+        box_triangles(Vec3::ZERO, Vec3::splat(2.0)),
+        Material::gem(),
+    );
+    let facets = cube.facets(1.0);
+    assert_eq!(facets.len(), 6);
+    for facet in facets.iter() {
+        assert_eq!(facet.triangles.len(), 2);
+        assert!((facet.area - 4.0).abs() < 1e-5);
+        // axis aligned and facing out
+        assert!((facet.normal.abs().max_element() - 1.0).abs() < 1e-6);
+        assert!(
+            facet
+                .normal
+                .dot(cube.centroid() - cube.triangle_slice()[facet.triangles[0]][0])
+                < 0.0
+        );
+    }
+    // opposite faces are 180° apart, neighbouring ones 90°
+    let angles: Vec<f32> = facets[1..]
+        .iter()
+        .map(|f| f.angle_from(&facets[0]))
+        .collect();
+    assert_eq!(
+        angles.iter().filter(|&&a| (a - 90.0).abs() < 1e-3).count(),
+        4
+    );
+    assert_eq!(
+        angles.iter().filter(|&&a| (a - 180.0).abs() < 1e-3).count(),
+        1
+    );
+}
+
+#[test]
+fn measures_crown_and_pavilion_angles() {
+    let stone = Mesh::from_tris_with_material(Vec3::ZERO, octagonal_stone(), Material::gem());
+    assert!(stone.validate().is_clean());
+    assert_eq!(stone.facets(0.5).len(), 17);
+
+    let report = stone.measure_angles(Vec3::Z).expect("no table facing +Z");
+    assert!(report.table_angle < 1e-3);
+    assert_eq!(report.crown_angles.len(), 8);
+    assert_eq!(report.pavilion_angles.len(), 8);
+    assert_eq!(report.girdle_facets, 0);
+    let crown = report.mean_crown_angle().unwrap();
+    let pavilion = report.mean_pavilion_angle().unwrap();
+    assert!((crown - 33.001).abs() < 0.01, "crown {crown}");
+    assert!((pavilion - 40.890).abs() < 0.01, "pavilion {pavilion}");
+
+    // upside down there's no table to measure from
+    assert!(stone.measure_angles(Vec3::NEG_Z).is_none());
+}
+
+#[test]
+fn ideal_stone_has_no_symmetry_deviation() {
+    // away from the origin, the axis through the stone is found from its shape
+    let stone =
+        Mesh::from_tris_with_material(vec3(3.0, -2.0, 1.0), octagonal_stone(), Material::gem());
+    for folds in [2, 4, 8] {
+        let report = stone.symmetry_report(Vec3::Z, folds);
+        assert_eq!(report.folds, folds);
+        assert!(report.max_deviation < 1e-4, "{folds} folds: {report:?}");
+    }
+    // an octagon doesn't map onto itself turned by a third
+    assert!(stone.symmetry_report(Vec3::Z, 3).max_deviation > 0.1);
+}
+
+#[test]
+fn misplaced_girdle_vertex_shows_as_deviation() {
+    let bumped = Vec3::X;
+    let triangles = octagonal_stone().into_iter().map(|t| {
+        let [a, b, c] = t.points().map(|p| if p == bumped { 1.1 * p } else { p });
+        Triangle::new(a, b, c)
+    });
+    let stone = Mesh::from_tris_with_material(Vec3::ZERO, triangles, Material::gem());
+    let report = stone.symmetry_report(Vec3::Z, 8);
+    assert!(report.max_deviation > 0.02, "{report:?}");
+    assert!(report.mean_deviation < report.max_deviation);
+}
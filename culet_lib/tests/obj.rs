@@ -0,0 +1,68 @@
+// OBJ import through Mesh::load_from_obj
+mod common;
+
+use common::temp_file;
+use culet_lib::{
+    hittable::Hittable,
+    mesh::{Mesh, MeshLoadError},
+    ray::Ray,
+};
+use glam::{vec2, vec3, Vec3};
+
+const SQUARE: &str = "# unit square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                      vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n";
+
+fn load(name: &str, contents: &str) -> Result<Mesh, MeshLoadError> {
+    let path = temp_file(name, contents.as_bytes());
+    let mesh = Mesh::load_from_obj(Vec3::ZERO, &path);
+    std::fs::remove_file(path).unwrap();
+    mesh
+}
+
+// texture coordinates at (x, y) on the square, hit from above
+fn uv_at(mesh: &Mesh, x: f32, y: f32) -> Option<glam::Vec2> {
+    let ray = Ray::new(vec3(x, y, 1.0), Vec3::NEG_Z);
+    mesh.hit_point(&ray, 0.0).expect("ray misses the square").uv
+}
+
+#[test]
+fn loads_quads_with_texture_coordinates() {
+    let mesh = load("uv.obj", &format!("{SQUARE}f 1/1/1 2/2/1 3/3/1 4/4/1\n")).unwrap();
+    assert_eq!(mesh.triangle_count(), 2);
+    assert!(mesh
+        .triangle_slice()
+        .iter()
+        .all(|triangle| triangle.normal() == Vec3::Z));
+    let uv = uv_at(&mesh, 0.25, 0.75).expect("no UVs on the hit");
+    assert!((uv - vec2(0.25, 0.75)).length() < 1e-5, "{uv}");
+}
+
+#[test]
+fn faces_without_texture_coordinates_have_no_uvs() {
+    for face in ["f 1 2 3 4", "f 1//1 2//1 3//1 4//1"] {
+        let mesh = load("plain.obj", &format!("{SQUARE}{face}\n")).unwrap();
+        assert_eq!(uv_at(&mesh, 0.25, 0.75), None, "{face}");
+    }
+}
+
+// negative indices count back from the latest vertex
+#[test]
+fn resolves_relative_indices() {
+    let mesh = load("relative.obj", &format!("{SQUARE}f -4/-4 -3/-3 -2/-2\n")).unwrap();
+    assert_eq!(mesh.triangle_count(), 1);
+    assert_eq!(
+        mesh.triangle_slice()[0].points(),
+        [Vec3::ZERO, Vec3::X, vec3(1.0, 1.0, 0.0)]
+    );
+}
+
+#[test]
+fn rejects_bad_faces() {
+    for face in ["f 1 2 5", "f 0 1 2", "f 1 2", "f 1/9 2/2 3/3", "f 1 2 x"] {
+        let result = load("bad.obj", &format!("{SQUARE}{face}\n"));
+        assert!(
+            matches!(result, Err(MeshLoadError::Obj(_))),
+            "`{face}`: {result:?}"
+        );
+    }
+}
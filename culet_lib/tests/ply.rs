@@ -0,0 +1,95 @@
+// PLY import through Mesh::load_from_ply
+mod common;
+
+use common::temp_file;
+use culet_lib::mesh::{Mesh, MeshLoadError};
+use glam::Vec3;
+
+const SQUARE: [Vec3; 4] = [
+    Vec3::new(0.0, 0.0, 0.0),
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+];
+
+fn header(format: &str, index_type: &str, faces: usize) -> String {
+    format!(
+        "ply\nformat {format} 1.0\ncomment unit square\nelement vertex 4\n\
+         property float x\nproperty float y\nproperty float z\nelement face {faces}\n\
+         property list uchar {index_type} vertex_indices\nend_header\n"
+    )
+}
+
+fn load(name: &str, contents: &[u8]) -> Result<Mesh, MeshLoadError> {
+    let path = temp_file(name, contents);
+    let mesh = Mesh::load_from_ply(Vec3::ZERO, &path);
+    std::fs::remove_file(path).unwrap();
+    mesh
+}
+
+fn ascii_square(index_type: &str, faces: &[&str]) -> String {
+    let mut ply = header("ascii", index_type, faces.len());
+    for p in SQUARE {
+        ply += &format!("{} {} {}\n", p.x, p.y, p.z);
+    }
+    for face in faces {
+        ply += face;
+        ply += "\n";
+    }
+    ply
+}
+
+// the square as one quad, fan triangulated into two triangles facing +Z
+fn assert_is_square(mesh: &Mesh) {
+    assert_eq!(mesh.triangle_count(), 2);
+    for triangle in mesh.triangle_slice() {
+        assert_eq!(triangle.normal(), Vec3::Z);
+        assert!(triangle.points().iter().all(|p| SQUARE.contains(p)));
+    }
+    assert!((mesh.triangle_slice().iter().map(|t| t.area()).sum::<f32>() - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn loads_ascii_quads() {
+    let mesh = load("ascii.ply", ascii_square("int", &["4 0 1 2 3"]).as_bytes()).unwrap();
+    assert_is_square(&mesh);
+}
+
+#[test]
+fn loads_binary_little_endian() {
+    let mut ply = header("binary_little_endian", "int", 1).into_bytes();
+    for p in SQUARE {
+        for coordinate in p.to_array() {
+            ply.extend(coordinate.to_le_bytes());
+        }
+    }
+    ply.push(4);
+    for index in [0i32, 1, 2, 3] {
+        ply.extend(index.to_le_bytes());
+    }
+    assert_is_square(&load("binary.ply", &ply).unwrap());
+}
+
+#[test]
+fn triangles_and_quads_mix() {
+    let faces = ["3 0 1 2", "4 0 1 2 3"];
+    let mesh = load("mixed.ply", ascii_square("uint", &faces).as_bytes()).unwrap();
+    assert_eq!(mesh.triangle_count(), 3);
+}
+
+#[test]
+fn rejects_bad_vertex_indices() {
+    let bad = [
+        ("int", "3 0 1 -1"),
+        ("float", "3 0 1 1.5"),
+        ("float", "3 0 1 nan"),
+        ("int", "3 0 1 4"),
+    ];
+    for (index_type, face) in bad {
+        let result = load("bad.ply", ascii_square(index_type, &[face]).as_bytes());
+        assert!(
+            matches!(result, Err(MeshLoadError::Ply(_))),
+            "`{face}` as {index_type}: {result:?}"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+// image-space post effects
+use culet_lib::{
+    post::{apply_bloom, chromatic_aberration, BloomParams, MAX_CHROMATIC_ABERRATION},
+    render::RenderOptions,
+};
+use glam::Vec3;
+
+#[test]
+fn bloom_spreads_a_bright_pixel_to_its_neighbours() {
+    let (width, height) = (15, 15);
+    let center = 7 * width + 7;
+    let mut buffer = vec![Vec3::ZERO; width * height];
+    buffer[center] = Vec3::splat(11.0);
+    let params = BloomParams {
+        threshold: 1.0,
+        intensity: 0.5,
+        radius: 1.5,
+    };
+    apply_bloom(&mut buffer, width, height, params);
+
+    let [left, right, up, down] = [center - 1, center + 1, center - width, center + width];
+    for neighbour in [left, right, up, down] {
+        assert!(buffer[neighbour].min_element() > 0.0, "pixel {neighbour}");
+        assert!((buffer[neighbour] - buffer[left]).abs().max_element() < 1e-6);
+    }
+    // the glow fades with distance
+    assert!(buffer[center - 2].x < buffer[left].x);
+    // and is the energy above the threshold, scaled by the intensity
+    let total: Vec3 = buffer.iter().sum();
+    let added = total - Vec3::splat(11.0);
+    assert!(
+        (added - Vec3::splat(10.0 * 0.5)).abs().max_element() < 1e-3,
+        "{added}"
+    );
+}
+
+#[test]
+fn chromatic_aberration_strength_is_clamped() {
+    let options = RenderOptions::new().chromatic_aberration(2.0);
+    assert_eq!(options.chromatic_aberration, MAX_CHROMATIC_ABERRATION);
+    let options = RenderOptions::new().chromatic_aberration(-1.0);
+    assert_eq!(options.chromatic_aberration, 0.0);
+
+    // unclamped, a strength of 1 would divide by zero when scaling the blue channel
+    let (width, height) = (8, 6);
+    let mut buffer: Vec<Vec3> = (0..width * height).map(|i| Vec3::splat(i as f32)).collect();
+    chromatic_aberration(&mut buffer, width, height, 1.0);
+    assert!(buffer.iter().all(|pixel| pixel.is_finite()));
+}
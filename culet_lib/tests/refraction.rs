@@ -0,0 +1,105 @@
+// physical invariants of the fresnel and refraction helpers the tracer is built on
+use culet_lib::render::{fresnel, refract};
+use glam::{vec3, Vec3};
+use proptest::prelude::*;
+
+const TOLERANCE: f32 = 1e-4;
+
+// the interface is the z = 0 plane with its normal facing the incoming ray
+const NORMAL: Vec3 = Vec3::Z;
+
+// unit direction hitting the interface `angle` radians from the normal
+fn incident(angle: f32) -> Vec3 {
+    vec3(angle.sin(), 0.0, -angle.cos())
+}
+
+fn refractive_index() -> impl Strategy<Value = f32> {
+    1.0f32..3.0
+}
+
+// just short of grazing, where reflectance goes to 1 whatever the media
+fn incident_angle() -> impl Strategy<Value = f32> {
+    0.0f32..1.5
+}
+
+proptest! {
+    #[test]
+    fn reflectance_is_a_fraction(
+        angle in incident_angle(),
+        eta_i in refractive_index(),
+        eta_t in refractive_index(),
+    ) {
+        let r = fresnel(incident(angle), NORMAL, eta_i, eta_t);
+        prop_assert!((0.0..=1.0).contains(&r), "reflectance {r}");
+    }
+
+    #[test]
+    fn total_internal_reflection_past_the_critical_angle(
+        eta_t in refractive_index(),
+        ratio in 1.05f32..2.0,
+        past in 0.0f32..1.0,
+    ) {
+        let eta_i = eta_t * ratio;
+        let critical = (eta_t / eta_i).asin();
+        let angle = critical + past * (std::f32::consts::FRAC_PI_2 - critical);
+        prop_assert_eq!(fresnel(incident(angle), NORMAL, eta_i, eta_t), 1.0);
+    }
+
+    #[test]
+    fn normal_incidence_is_symmetric_in_the_media(
+        eta_i in refractive_index(),
+        eta_t in refractive_index(),
+    ) {
+        let forward = fresnel(-NORMAL, NORMAL, eta_i, eta_t);
+        let backward = fresnel(-NORMAL, NORMAL, eta_t, eta_i);
+        let expected = ((eta_i - eta_t) / (eta_i + eta_t)).powi(2);
+        prop_assert!((forward - backward).abs() < TOLERANCE);
+        prop_assert!((forward - expected).abs() < TOLERANCE);
+    }
+
+    // the transmitted power from the Fresnel transmission coefficients, independent of how
+    // `fresnel` computes reflectance, makes up the rest
+    #[test]
+    fn reflected_and_transmitted_energy_sum_to_one(
+        angle in incident_angle(),
+        eta_i in refractive_index(),
+        eta_t in refractive_index(),
+    ) {
+        let cos_i = angle.cos();
+        let sin_t = eta_i / eta_t * angle.sin();
+        prop_assume!(sin_t < 1.0);
+        let cos_t = (1.0 - sin_t * sin_t).sqrt();
+
+        let t_s = 2.0 * eta_i * cos_i / (eta_i * cos_i + eta_t * cos_t);
+        let t_p = 2.0 * eta_i * cos_i / (eta_t * cos_i + eta_i * cos_t);
+        let transmitted = (eta_t * cos_t) / (eta_i * cos_i) * (t_s * t_s + t_p * t_p) / 2.0;
+        let reflected = fresnel(incident(angle), NORMAL, eta_i, eta_t);
+        prop_assert!(
+            (reflected + transmitted - 1.0).abs() < TOLERANCE,
+            "reflected {reflected} + transmitted {transmitted}"
+        );
+    }
+
+    #[test]
+    fn refraction_obeys_snells_law(
+        angle in incident_angle(),
+        eta_i in refractive_index(),
+        eta_t in refractive_index(),
+    ) {
+        let direction = incident(angle);
+        prop_assume!(eta_i / eta_t * angle.sin() < 1.0);
+        let refracted = refract(direction, NORMAL, eta_i / eta_t);
+
+        prop_assert!((refracted.length() - 1.0).abs() < TOLERANCE);
+        // carries on through the interface, in the plane of incidence
+        prop_assert!(refracted.z < 0.0);
+        prop_assert!(refracted.y.abs() < TOLERANCE);
+        let sin_t = refracted.cross(NORMAL).length();
+        prop_assert!(
+            (eta_i * angle.sin() - eta_t * sin_t).abs() < TOLERANCE,
+            "{eta_i} sin {} != {eta_t} sin {}",
+            angle.sin(),
+            sin_t
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// end to end behaviour of the CPU tracer on small hand-built scenes
+mod common;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use common::{box_mesh, gem};
+use culet_lib::{
+    material::Material,
+    ray::Ray,
+    render::{RenderMsg, RenderOptions},
+    scene::Scene,
+    sphere::Sphere,
+};
+use glam::{vec3, Vec3};
+use proptest::prelude::*;
+use rand::{rngs::SmallRng, SeedableRng};
+
+// an absorbing glass cube around the origin, seen against a white background through
+// every face it refracts out of
+fn glass_cube_options() -> RenderOptions {
+    let cube = box_mesh(
+        Vec3::splat(-0.5),
+        Vec3::splat(0.5),
+        gem(1.5, Vec3::splat(0.5)),
+    );
+    RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![cube])))
+        .background_color(Vec3::ONE)
+        .refracted_background(true)
+}
+
+// mean of `samples` traces of `ray`, each with its own random stream
+fn mean_trace(options: &RenderOptions, ray: &Ray, samples: usize) -> Vec3 {
+    let mut rng = SmallRng::seed_from_u64(0x5eed);
+    let sum: Vec3 = (0..samples)
+        .map(|_| options.trace(ray, options.max_bounces, &mut rng))
+        .sum();
+    sum / samples as f32
+}
+
+// roulette from the first bounce must converge on the same color as never playing it,
+// even when the bounce budget is too small for the paths to fade out on their own
+#[test]
+fn russian_roulette_keeps_low_bounce_renders_unbiased() {
+    let ray = Ray::new(vec3(2.0, 0.1, 0.05), vec3(-1.0, 0.0, 0.0));
+    let bounces = 4;
+    let reference = glass_cube_options()
+        .max_bounces(bounces)
+        .rr_start_bounce(usize::MAX);
+    let roulette = glass_cube_options().max_bounces(bounces).rr_start_bounce(0);
+
+    let expected = mean_trace(&reference, &ray, 1);
+    let actual = mean_trace(&roulette, &ray, 50_000);
+    assert!(expected.min_element() > 0.1, "reference {expected}");
+    common::assert_close(actual, expected, 0.02);
+}
+
+// a direction that normalizes to zero can't hit anything, and mustn't turn into NaN
+// on the way out either
+#[test]
+fn zero_length_ray_traces_to_a_finite_color() {
+    let options = glass_cube_options().max_bounces(4);
+    let mut rng = SmallRng::seed_from_u64(0);
+    for origin in [vec3(2.0, 0.0, 0.0), Vec3::ZERO] {
+        let ray = Ray::new(origin, Vec3::ZERO);
+        let color = options.trace(&ray, options.max_bounces, &mut rng);
+        assert!(color.is_finite(), "{color} from {origin}");
+    }
+}
+
+// odd sizes so the pixel groups and per-thread chunks don't divide the image evenly
+#[test]
+fn streaming_render_sends_every_pixel_once() {
+    let (width, height) = (13, 7);
+    let options = glass_cube_options()
+        .image_width(width)
+        .image_height(height)
+        .threads(3);
+    let (rx, _handle) = options.render_streaming();
+
+    let mut sent = vec![0; width * height];
+    loop {
+        match rx.recv().expect("workers hung up before Done") {
+            RenderMsg::Pixel { x, y, .. } => sent[y as usize * width + x as usize] += 1,
+            RenderMsg::Done => break,
+            RenderMsg::Abort => panic!("render aborted"),
+        }
+    }
+    assert!(sent.iter().all(|&count| count == 1), "{sent:?}");
+}
+
+// a glowing box in the middle of a 16x9 view, against a black background
+fn lamp_options() -> RenderOptions {
+    let light = Material::Light { color: Vec3::ONE };
+    let lamp = box_mesh(vec3(-0.5, -0.5, -3.0), vec3(0.5, 0.5, -2.0), light);
+    RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![lamp])))
+        .background_color(Vec3::ZERO)
+        .image_width(16)
+        .image_height(9)
+}
+
+// the alpha streamed with each pixel checked against a separate matte pass. one unjittered
+// sample makes the two trace the same primary rays, with and without packets
+#[test]
+fn streamed_alpha_matches_the_matte() {
+    let (width, height) = (16, 9);
+    for ray_packets in [false, true] {
+        let options = lamp_options().ray_packets(ray_packets);
+        let matte = options.render_matte();
+        assert!(matte.contains(&0.0) && matte.contains(&1.0), "{matte:?}");
+
+        let (rx, _handle) = options.render_streaming();
+        let mut alpha = vec![-1.0; width * height];
+        loop {
+            match rx.recv().expect("workers hung up before Done") {
+                RenderMsg::Pixel { x, y, alpha: a, .. } => {
+                    alpha[y as usize * width + x as usize] = a;
+                }
+                RenderMsg::Done => break,
+                RenderMsg::Abort => panic!("render aborted"),
+            }
+        }
+        assert_eq!(alpha, matte, "ray_packets {ray_packets}");
+    }
+}
+
+// samples are seeded by pixel, so jittered edges come out the same whichever thread traces
+// them, and only a different seed moves them
+#[test]
+fn jittered_render_is_reproducible_for_a_seed() {
+    let options = lamp_options()
+        .jitter_first_sample(true)
+        .samples_per_pixel(2);
+    let single = options.clone().threads(1).render();
+    assert_eq!(options.clone().threads(3).render(), single);
+    assert_ne!(options.seed(1).threads(3).render(), single);
+}
+
+// a camera ray that misses everything shows the background however many bounces the caller
+// traces it with, not just the configured max_bounces
+#[test]
+fn primary_miss_shows_the_background_for_any_bounce_count() {
+    let background = vec3(0.2, 0.4, 0.6);
+    let options = RenderOptions::new()
+        .background_color(background)
+        .max_bounces(8);
+    let mut rng = SmallRng::seed_from_u64(0);
+    let ray = Ray::new(Vec3::ZERO, Vec3::NEG_Z);
+    for bounces in [1, 3, 8, 12] {
+        assert_eq!(
+            options.trace(&ray, bounces, &mut rng),
+            background,
+            "{bounces}"
+        );
+    }
+}
+
+// workers waiting on a full channel for a consumer that stopped reading have to notice the
+// abort, instead of staying blocked with the receiver still alive
+#[test]
+fn abort_releases_workers_blocked_on_a_full_channel() {
+    let options = glass_cube_options()
+        .image_width(16)
+        .image_height(16)
+        .threads(2)
+        .channel_capacity(1);
+    let (rx, handle) = options.render_streaming();
+    assert!(matches!(rx.recv(), Ok(RenderMsg::Pixel { .. })));
+
+    handle.abort();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !handle.is_finished() {
+        assert!(
+            Instant::now() < deadline,
+            "workers still blocked after the abort"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    // the channel never held more than its capacity
+    let queued = std::iter::from_fn(|| rx.try_recv().ok()).count();
+    assert!(queued <= 1, "{queued} messages queued");
+}
+
+// glowing boxes 1 and 5 units along -X, side by side so neither hides the other
+#[test]
+fn clip_far_culls_meshes_beyond_it() {
+    let light = Material::Light { color: Vec3::ONE };
+    let near = box_mesh(vec3(-2.0, -0.5, -0.5), vec3(-1.0, 0.5, 0.5), light);
+    let far = box_mesh(vec3(-6.0, 1.5, -0.5), vec3(-5.0, 2.5, 0.5), light);
+    let options = RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![near, far])))
+        .background_color(Vec3::ZERO)
+        .clip_far(3.0);
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut trace = |origin: Vec3| {
+        let ray = Ray::new(origin, Vec3::NEG_X);
+        options.trace(&ray, options.max_bounces, &mut rng)
+    };
+
+    assert_eq!(trace(Vec3::ZERO), Vec3::ONE);
+    assert_eq!(trace(vec3(0.0, 2.0, 0.0)), Vec3::ZERO);
+    // and without the far clip both are seen
+    let options = options.clip_far(f32::INFINITY);
+    let ray = Ray::new(vec3(0.0, 2.0, 0.0), Vec3::NEG_X);
+    assert_eq!(
+        options.trace(&ray, options.max_bounces, &mut rng),
+        Vec3::ONE
+    );
+}
+
+#[test]
+fn channel_capacity_is_at_least_one() {
+    assert_eq!(RenderOptions::new().channel_capacity(0).channel_capacity, 1);
+    assert_eq!(RenderOptions::new().channel_capacity(8).channel_capacity, 8);
+}
+
+#[test]
+fn renders_empty_and_single_pixel_images() {
+    // the builders bump zero to one pixel, so an empty image needs the fields set directly
+    let mut options = glass_cube_options();
+    options.image_width = 0;
+    options.image_height = 0;
+    assert!(options.render().is_empty());
+
+    let options = glass_cube_options().image_width(0).image_height(0);
+    assert_eq!((options.image_width, options.image_height), (1, 1));
+    let image = options.render();
+    assert_eq!(image.len(), 1);
+    assert!(image[0].is_finite(), "{}", image[0]);
+}
+
+// a glass cube between the camera and a small light, seen at an angle so refraction at its
+// faces would bend the ray away from the light
+fn light_behind_glass(exterior_ri: f32) -> Vec3 {
+    let cube = box_mesh(Vec3::splat(-0.5), Vec3::splat(0.5), gem(1.5, Vec3::ZERO));
+    let light = box_mesh(
+        vec3(-2.2, 1.0, -0.2),
+        vec3(-2.0, 1.4, 0.2),
+        Material::Light { color: Vec3::ONE },
+    );
+    let options = RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![cube, light])))
+        .exterior_ri(exterior_ri)
+        .background_color(Vec3::ZERO)
+        .refracted_background(true)
+        .max_bounces(8);
+    // straight through the cube this reaches the light at (-2, 1.2, 0)
+    let ray = Ray::new(vec3(2.0, 0.0, 0.0), vec3(-1.0, 0.3, 0.0));
+    let mut rng = SmallRng::seed_from_u64(0);
+    options.trace(&ray, options.max_bounces, &mut rng)
+}
+
+#[test]
+fn gem_vanishes_in_a_medium_of_its_own_index() {
+    common::assert_close(light_behind_glass(1.5), Vec3::ONE, 1e-4);
+    // in air the ray bends at the faces and misses the light
+    assert!(light_behind_glass(1.0).max_element() < 0.5);
+}
+
+// colors along a fan of rays in the z = 0 plane through spheres at the origin, towards a
+// light strip behind them
+fn through_spheres(exterior_ri: f32, spheres: &[Sphere]) -> Vec<Vec3> {
+    let light = box_mesh(
+        vec3(-3.2, 0.2, -1.0),
+        vec3(-3.0, 0.6, 1.0),
+        Material::Light { color: Vec3::ONE },
+    );
+    let mut scene = Scene::new(vec![light]);
+    for &sphere in spheres {
+        scene.push_sphere(sphere);
+    }
+    let options = RenderOptions::new()
+        .scene(Arc::new(scene))
+        .exterior_ri(exterior_ri)
+        .background_color(Vec3::ZERO)
+        .refracted_background(true)
+        // crossing an invisible interface still uses up a bounce, so leave enough that paths
+        // end by fading out rather than by running out
+        .max_bounces(64)
+        .rr_start_bounce(usize::MAX);
+    let mut rng = SmallRng::seed_from_u64(0);
+    (-9..=9)
+        .map(|i| {
+            let ray = Ray::new(vec3(3.0, i as f32 * 0.1, 0.0), vec3(-1.0, 0.1, 0.0));
+            options.trace(&ray, options.max_bounces, &mut rng)
+        })
+        .collect()
+}
+
+fn assert_same_image(actual: &[Vec3], expected: &[Vec3]) {
+    for (&a, &e) in actual.iter().zip(expected) {
+        common::assert_close(a, e, 1e-3);
+    }
+}
+
+// each interface refracts by the ratio of the media either side of it, so an inclusion of
+// the stone's own index is invisible, and so is a stone of the exterior's index around one
+#[test]
+fn nested_spheres_refract_by_the_surrounding_medium() {
+    let outer = |ri| Sphere::new(Vec3::ZERO, 1.0, gem(ri, Vec3::ZERO));
+    let inner = |ri| Sphere::new(vec3(0.1, 0.0, 0.0), 0.5, gem(ri, Vec3::ZERO));
+
+    // stone to inclusion is 1.5 to 1.5, not the exterior's 1.0 to 1.5
+    let stone = through_spheres(1.0, &[outer(1.5)]);
+    assert!(stone.iter().any(|c| c.max_element() > 0.0));
+    assert_same_image(&through_spheres(1.0, &[outer(1.5), inner(1.5)]), &stone);
+
+    // the inclusion sits in the stone's 1.5 either way, whether or not the stone is there
+    let inclusion = through_spheres(1.5, &[inner(2.0)]);
+    assert_same_image(&through_spheres(1.5, &[outer(1.5), inner(2.0)]), &inclusion);
+}
+
+// `thickness` of absorbing glass starting at the origin and extending along `direction`,
+// wide enough across it that a ray along `direction` near the origin can't miss
+fn slab_along(direction: Vec3, thickness: f32, absorption: Vec3) -> RenderOptions {
+    let across = Vec3::ONE - direction.abs();
+    let far_face = direction * thickness;
+    let slab = box_mesh(
+        far_face.min(Vec3::ZERO) - across * 50.0,
+        far_face.max(Vec3::ZERO) + across * 50.0,
+        gem(1.5, absorption),
+    );
+    RenderOptions::new()
+        .scene(Arc::new(Scene::new(vec![slab])))
+        .exterior_ri(1.5)
+        .background_color(Vec3::ONE)
+        .refracted_background(true)
+        .max_bounces(2)
+}
+
+proptest! {
+    // a slab of the same index as its surroundings, so no light is reflected at either face
+    // and all the transmitted ray loses is absorption over the thickness it crossed. which
+    // way the slab faces mustn't matter, including straight up and down
+    #[test]
+    fn slab_attenuates_by_beers_law(
+        thickness in 0.05f32..3.0,
+        absorption in (0.0f32..2.0, 0.0f32..2.0, 0.0f32..2.0),
+    ) {
+        let absorption = Vec3::from(absorption);
+        let expected = (-absorption * thickness).exp();
+        for direction in [Vec3::NEG_X, Vec3::Y, Vec3::NEG_Z, Vec3::Z] {
+            let options = slab_along(direction, thickness, absorption);
+            let across = Vec3::ONE - direction.abs();
+            let ray = Ray::new(-direction + across * vec3(0.3, 0.2, 0.1), direction);
+            let mut rng = SmallRng::seed_from_u64(0);
+            let transmitted = options.trace(&ray, options.max_bounces, &mut rng);
+
+            prop_assert!(
+                (transmitted - expected).abs().max_element() < 1e-4,
+                "{transmitted} along {direction} through {thickness} of {absorption}, \
+                 expected {expected}"
+            );
+        }
+    }
+}
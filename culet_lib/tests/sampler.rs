@@ -0,0 +1,34 @@
+// how the pixel samplers place their samples
+use culet_lib::sampler::{stratified_grid, Sampler, SamplerKind};
+use glam::Vec2;
+use rand::{rngs::SmallRng, SeedableRng};
+
+#[test]
+fn stratified_grid_has_a_cell_per_sample() {
+    assert_eq!(stratified_grid(1), (1, 1));
+    assert_eq!(stratified_grid(16), (4, 4));
+    assert_eq!(stratified_grid(8), (4, 2));
+    assert_eq!(stratified_grid(7), (7, 1));
+    for count in 1..100 {
+        let (columns, rows) = stratified_grid(count);
+        assert_eq!(columns * rows, count);
+        assert!(columns >= rows);
+    }
+}
+
+#[test]
+fn stratified_samples_land_one_per_cell() {
+    let mut rng = SmallRng::seed_from_u64(7);
+    for count in [1, 4, 6, 9, 12, 16, 64] {
+        let (columns, rows) = stratified_grid(count);
+        let mut cells = vec![0; count];
+        for index in 0..count {
+            let offset = SamplerKind::Stratified.pixel_offset(3, index, count, &mut rng);
+            assert!(offset.cmpge(Vec2::splat(-0.5)).all() && offset.cmplt(Vec2::splat(0.5)).all());
+            let column = ((offset.x + 0.5) * columns as f32) as usize;
+            let row = ((offset.y + 0.5) * rows as f32) as usize;
+            cells[row * columns + column] += 1;
+        }
+        assert!(cells.iter().all(|&n| n == 1), "{count} samples: {cells:?}");
+    }
+}
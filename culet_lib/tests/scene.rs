@@ -0,0 +1,41 @@
+// bookkeeping the scene derives from its meshes
+mod common;
+
+use common::box_mesh;
+use culet_lib::{material::Material, scene::Scene};
+use glam::Vec3;
+
+fn light(intensity: f32) -> Material {
+    Material::Light {
+        color: Vec3::splat(intensity),
+    }
+}
+
+#[test]
+fn modifying_meshes_refreshes_the_lights() {
+    let mut scene = Scene::new(vec![box_mesh(Vec3::ZERO, Vec3::ONE, light(1.0))]);
+    assert!((scene.light_area() - 6.0).abs() < 1e-4);
+
+    scene.modify_meshes(|meshes| {
+        meshes[0].set_material(Material::Diffuse {
+            color: Vec3::ONE,
+            roughness: 0.0,
+        })
+    });
+    assert_eq!(scene.light_area(), 0.0);
+}
+
+#[test]
+fn mutable_meshes_refresh_the_lights_when_dropped() {
+    let mut scene = Scene::new(vec![box_mesh(Vec3::ZERO, Vec3::ONE, light(1.0))]);
+    for mesh in scene.meshes_mut().iter_mut() {
+        mesh.set_material(light(2.0));
+    }
+    assert!((scene.light_area() - 6.0).abs() < 1e-4);
+
+    scene.meshes_mut()[0].set_material(Material::Diffuse {
+        color: Vec3::ONE,
+        roughness: 0.0,
+    });
+    assert_eq!(scene.light_area(), 0.0);
+}